@@ -0,0 +1,273 @@
+use std::iter::{Enumerate, FilterMap};
+use std::slice;
+
+use crate::magic::{bishop_attacks, queen_attacks, rook_attacks};
+use crate::play::castle_rook_squares;
+use crate::structs::*;
+use crate::zobrist::piece_key;
+
+/// A set of squares, one bit per square with a1 = bit 0 and h8 = bit 63.
+pub type Bitboard = u64;
+
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(&Directions::KNIGHT);
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_attacks(&Directions::KING);
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    leaper_attacks(&[(-1, 1), (1, 1)]),
+    leaper_attacks(&[(-1, -1), (1, -1)]),
+];
+
+const fn leaper_attacks(offsets: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let file = (index % 8) as i8;
+        let rank = (index / 8) as i8;
+        let mut i = 0;
+        while i < offsets.len() {
+            let (file_offset, rank_offset) = offsets[i];
+            let (target_file, target_rank) = (file + file_offset, rank + rank_offset);
+            if target_file >= 0 && target_file < 8 && target_rank >= 0 && target_rank < 8 {
+                table[index] |= 1 << (target_rank * 8 + target_file);
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+/// Iterates over the squares of a bitboard from a1 towards h8.
+pub struct Squares(pub Bitboard);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(Square::from_index(index))
+    }
+}
+
+/// Read-only view over the pieces of a [`Board`], shaped like the map it replaced.
+pub struct Pieces<'a> {
+    mailbox: &'a [Option<Piece>; 64],
+}
+
+impl<'a> Pieces<'a> {
+    pub fn get(&self, square: &Square) -> Option<&'a Piece> {
+        self.mailbox[square.index()].as_ref()
+    }
+
+    pub fn iter(&self) -> PiecesIter<'a> {
+        self.mailbox.iter().enumerate().filter_map(occupied_entry)
+    }
+}
+
+pub type PiecesIter<'a> = FilterMap<
+    Enumerate<slice::Iter<'a, Option<Piece>>>,
+    fn((usize, &'a Option<Piece>)) -> Option<(Square, &'a Piece)>,
+>;
+
+fn occupied_entry((index, piece): (usize, &Option<Piece>)) -> Option<(Square, &Piece)> {
+    piece.as_ref().map(|p| (Square::from_index(index), p))
+}
+
+impl<'a> IntoIterator for Pieces<'a> {
+    type Item = (Square, &'a Piece);
+    type IntoIter = PiecesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Board {
+    pub(crate) fn empty() -> Board {
+        Board {
+            history: vec![],
+            bitboards: [[0; 6]; 2],
+            occupancy: [0; 2],
+            mailbox: [None; 64],
+
+            turn: White,
+            castling_rights: [CastlingRights::new(false, false); 2],
+            enpassant_square: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+        }
+    }
+
+    pub fn pieces(&self) -> Pieces<'_> {
+        Pieces {
+            mailbox: &self.mailbox,
+        }
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.occupancy[White as usize] | self.occupancy[Black as usize]
+    }
+
+    pub fn bitboard(&self, color: Color, r#type: PieceType) -> Bitboard {
+        self.bitboards[color as usize][r#type as usize]
+    }
+
+    pub fn king_square(&self, color: Color) -> Square {
+        Square::from_index(self.bitboard(color, King).trailing_zeros() as usize)
+    }
+
+    pub(crate) fn put_piece(&mut self, square: Square, piece: Piece) {
+        let bit = square.bitboard();
+        self.bitboards[piece.color as usize][piece.r#type as usize] |= bit;
+        self.occupancy[piece.color as usize] |= bit;
+        self.mailbox[square.index()] = Some(piece);
+        self.hash ^= piece_key(&piece, square);
+    }
+
+    pub(crate) fn remove_piece(&mut self, square: Square) -> Piece {
+        let piece = self.mailbox[square.index()]
+            .take()
+            .expect("No piece on square");
+        let bit = square.bitboard();
+        self.bitboards[piece.color as usize][piece.r#type as usize] &= !bit;
+        self.occupancy[piece.color as usize] &= !bit;
+        self.hash ^= piece_key(&piece, square);
+        piece
+    }
+
+    pub(crate) fn move_piece(&mut self, from: Square, to: Square) -> Piece {
+        let piece = self.remove_piece(from);
+        self.put_piece(to, piece);
+        piece
+    }
+
+    /// Every piece of either color attacking `square`, given the occupancy `occupied`.
+    pub fn attackers_to(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let index = square.index();
+        let bishops = self.bitboard(White, Bishop)
+            | self.bitboard(Black, Bishop)
+            | self.bitboard(White, Queen)
+            | self.bitboard(Black, Queen);
+        let rooks = self.bitboard(White, Rook)
+            | self.bitboard(Black, Rook)
+            | self.bitboard(White, Queen)
+            | self.bitboard(Black, Queen);
+
+        (PAWN_ATTACKS[Black as usize][index] & self.bitboard(White, Pawn))
+            | (PAWN_ATTACKS[White as usize][index] & self.bitboard(Black, Pawn))
+            | (KNIGHT_ATTACKS[index] & (self.bitboard(White, Knight) | self.bitboard(Black, Knight)))
+            | (KING_ATTACKS[index] & (self.bitboard(White, King) | self.bitboard(Black, King)))
+            | (bishop_attacks(square, occupied) & bishops)
+            | (rook_attacks(square, occupied) & rooks)
+    }
+
+    pub fn is_attacked(&self, square: Square, by: Color) -> bool {
+        self.attackers_to(square, self.occupied()) & self.occupancy[by as usize] != 0
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.is_attacked(self.king_square(self.turn), self.turn.opposite())
+    }
+
+    /// Whether playing the legal move `r#move` checks the opponent, either
+    /// with the piece that moved or by uncovering a slider behind it.
+    pub fn gives_check(&self, r#move: &Move) -> bool {
+        let us = self.turn;
+        let king = self.king_square(us.opposite()).bitboard();
+        let mut movers = r#move.from.bitboard();
+        let mut occupied = (self.occupied() & !movers) | r#move.to.bitboard();
+        let mut square = r#move.to;
+        let mut r#type = match r#move.promotion {
+            Some(promotion) => promotion,
+            None => self.pieces().get(&r#move.from).map_or(King, |piece| piece.r#type),
+        };
+
+        match r#move.r#type {
+            Enpassant => {
+                occupied &= !Square {
+                    file: r#move.to.file,
+                    rank: r#move.from.rank,
+                }
+                .bitboard();
+            }
+            // Only the rook can give check after castling
+            Castle => {
+                let (rook_from, rook_to) = castle_rook_squares(r#move);
+                movers |= rook_from.bitboard();
+                occupied = (occupied & !rook_from.bitboard()) | rook_to.bitboard();
+                square = rook_to;
+                r#type = Rook;
+            }
+            Null => return false,
+            _ => {}
+        }
+
+        let attacks = match r#type {
+            Pawn => PAWN_ATTACKS[us as usize][square.index()],
+            Knight => KNIGHT_ATTACKS[square.index()],
+            Bishop => bishop_attacks(square, occupied),
+            Rook => rook_attacks(square, occupied),
+            Queen => queen_attacks(square, occupied),
+            King => 0,
+        };
+        if attacks & king != 0 {
+            return true;
+        }
+
+        let king = self.king_square(us.opposite());
+        let diagonal = (self.bitboard(us, Bishop) | self.bitboard(us, Queen)) & !movers;
+        let straight = (self.bitboard(us, Rook) | self.bitboard(us, Queen)) & !movers;
+        bishop_attacks(king, occupied) & diagonal != 0 || rook_attacks(king, occupied) & straight != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::STARTING_FEN;
+    use crate::structs::Board;
+    use crate::test_positions::{for_each_position, KIWIPETE, POSITION_3, POSITION_4, POSITION_5};
+
+
+    /// Checks `gives_check` against playing the move, for every move up to
+    /// three plies from the perft positions.
+    #[test]
+    fn gives_check_matches_playing_the_move() {
+        for fen in [STARTING_FEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            for_each_position(&mut Board::from_fen(fen.to_string()).unwrap(), 2, &mut |board| {
+                for r#move in board.get_moves(false).0 {
+                    let predicted = board.gives_check(&r#move);
+                    let uci = r#move.to_uci();
+                    board.execute(r#move);
+                    assert_eq!(predicted, board.in_check(), "{} in {}", uci, board.to_fen());
+                    board.undo();
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn discovered_checks() {
+        let checks = [
+            // En passant uncovers the rook behind the capturing pawn
+            ("4r1k1/8/8/8/3Pp3/8/8/4K3 b - d3 0 1", "e4d3"),
+            // Castling checks with the rook
+            ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"),
+            // The knight uncovers the rook
+            ("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1", "e2c3"),
+        ];
+        for (fen, uci) in checks {
+            let board = Board::from_fen(fen.to_string()).unwrap();
+            let r#move = board
+                .get_moves(false)
+                .0
+                .into_iter()
+                .find(|m| m.to_uci() == uci)
+                .unwrap();
+            assert!(board.gives_check(&r#move), "{} in {}", uci, fen);
+        }
+    }
+}
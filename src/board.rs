@@ -1,5 +1,7 @@
-use chess_engine::square;
+use chess_engine_macros::square;
 use structs::*;
+use crate::bitboard::*;
+use crate::magic::*;
 use crate::structs;
 
 impl Board {
@@ -7,7 +9,7 @@ impl Board {
         let mut board_rep = [['.'; 8]; 8]; // Initialize the board with empty squares
 
         // Populate the board with pieces
-        for (square, piece) in &self.pieces {
+        for (square, piece) in self.pieces() {
             let rank_idx = 7 - Into::<i8>::into(square.rank) as usize; // Invert rank for display (top-down)
             let file_idx = Into::<i8>::into(square.file) as usize;
 
@@ -42,356 +44,187 @@ impl Board {
         // Print file letters at the bottom
         println!("  a b c d e f g h");
     }
-    pub fn get_square(&self, piece: &Piece) -> Option<Square> {
-        self.pieces
-            .iter()
-            .find_map(|(s, p)| if p.id == piece.id { Some(*s) } else { None })
-    }
     pub fn get_moves(&self, only_captures: bool) -> (Vec<Move>, bool) {
-        let mut moves = vec![];
-        let opposite_color = self.turn.opposite();
-
-        let piece_rank = self.turn.get_piece_rank();
-        let mut in_check = false;
-        let mut unchecked_squares = vec![
-            square!(C piece_rank),
-            square!(D piece_rank),
-            square!(F piece_rank),
-            square!(G piece_rank),
-        ];
+        let mut moves = Vec::with_capacity(64);
+        let in_check = self.in_check();
 
-        for (square, piece) in &self.pieces {
-            if piece.color == opposite_color {
-                continue;
+        let own = self.occupancy[self.turn as usize];
+        for square in Squares(own) {
+            let piece = self.mailbox[square.index()].unwrap();
+            match piece.r#type {
+                Pawn => self.get_pawn_moves(&mut moves, square, only_captures),
+                Knight => self.get_target_moves(
+                    &mut moves,
+                    square,
+                    KNIGHT_ATTACKS[square.index()],
+                    only_captures,
+                ),
+                Bishop => self.get_target_moves(
+                    &mut moves,
+                    square,
+                    bishop_attacks(square, self.occupied()),
+                    only_captures,
+                ),
+                Rook => self.get_target_moves(
+                    &mut moves,
+                    square,
+                    rook_attacks(square, self.occupied()),
+                    only_captures,
+                ),
+                Queen => self.get_target_moves(
+                    &mut moves,
+                    square,
+                    queen_attacks(square, self.occupied()),
+                    only_captures,
+                ),
+                King => self.get_target_moves(
+                    &mut moves,
+                    square,
+                    KING_ATTACKS[square.index()],
+                    only_captures,
+                ),
             }
+        }
 
-            let square = *square;
-            match piece.r#type {
-                Pawn => {
-                    let multiplier = self.turn.get_multiplier();
+        if !only_captures && !in_check {
+            self.get_castling_moves(&mut moves);
+        }
 
-                    if !only_captures && square.rank == self.turn.get_pawn_rank()
-                        && self
-                            .pieces
-                            .get(&square.offset(0, multiplier).unwrap())
-                            .is_none()
-                        && self
-                            .pieces
-                            .get(&square.offset(0, 2 * multiplier).unwrap())
-                            .is_none()
-                    {
-                        moves.push(Move::from_pawn_jump(
-                            square,
-                            square.offset(0, 2 * multiplier).unwrap(),
-                        ));
-                    }
+        moves.retain(|m| self.is_legal(m));
+        (moves, in_check)
+    }
 
-                    if square.rank == self.turn.opposite().get_pawn_rank() {
-                        if let Some(target_square) = square.offset(-1, multiplier) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    for r#type in [Queen, Rook, Bishop, Knight] {
-                                        moves.push(Move::from_promotion_capture(
-                                            square,
-                                            target_square,
-                                            target_piece.clone(),
-                                            r#type,
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                        let target_square = square.offset(0, multiplier).unwrap();
-                        if !only_captures && self.pieces.get(&target_square).is_none() {
-                            for r#type in [Queen, Rook, Bishop, Knight] {
-                                moves.push(Move::from_promotion(square, target_square, r#type));
-                            }
-                        }
-                        if let Some(target_square) = square.offset(1, multiplier) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    for r#type in [Queen, Rook, Bishop, Knight] {
-                                        moves.push(Move::from_promotion_capture(
-                                            square,
-                                            target_square,
-                                            target_piece.clone(),
-                                            r#type,
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        if let Some(enpassant_square) = self.enpassant_square {
-                            if enpassant_square.rank == self.turn.opposite().get_enpassant_rank()
-                                && square.rank == self.turn.opposite().get_center_rank()
-                            {
-                                if let Some(left_target_square) = square.offset(-1, multiplier) {
-                                    if left_target_square == enpassant_square {
-                                        moves.push(Move::from_enpassant(
-                                            square,
-                                            left_target_square,
-                                            self.pieces
-                                                .get(
-                                                    &enpassant_square
-                                                        .offset(0, -multiplier)
-                                                        .unwrap(),
-                                                )
-                                                .unwrap()
-                                                .clone(),
-                                        ));
-                                    }
-                                }
-                                if let Some(right_target_square) = square.offset(1, multiplier) {
-                                    if right_target_square == enpassant_square {
-                                        moves.push(Move::from_enpassant(
-                                            square,
-                                            right_target_square,
-                                            self.pieces
-                                                .get(
-                                                    &enpassant_square
-                                                        .offset(0, -multiplier)
-                                                        .unwrap(),
-                                                )
-                                                .unwrap()
-                                                .clone(),
-                                        ));
-                                    }
-                                }
-                            }
-                        }
-                        if let Some(target_square) = square.offset(-1, multiplier) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    moves.push(Move::from_capture(
-                                        square,
-                                        target_square,
-                                        target_piece.clone(),
-                                    ));
-                                }
-                            }
-                        }
-                        let target_square = square.offset(0, multiplier).unwrap();
-                        if !only_captures && self.pieces.get(&target_square).is_none() {
-                            moves.push(Move::from_normal(square, target_square));
-                        }
-                        if let Some(target_square) = square.offset(1, multiplier) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    moves.push(Move::from_capture(
-                                        square,
-                                        target_square,
-                                        target_piece.clone(),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                }
-                Knight => {
-                    for (file, rank) in Directions::KNIGHT {
-                        if let Some(target_square) = square.offset(file, rank) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    moves.push(Move::from_capture(
-                                        square,
-                                        target_square,
-                                        target_piece.clone(),
-                                    ));
-                                }
-                            } else if !only_captures {
-                                moves.push(Move::from_normal(square, target_square));
-                            }
-                        }
-                    }
+    fn get_pawn_moves(&self, moves: &mut Vec<Move>, square: Square, only_captures: bool) {
+        let multiplier = self.turn.get_multiplier();
+        let enemies = self.occupancy[self.turn.opposite() as usize];
+        let promotes = square.rank == self.turn.opposite().get_pawn_rank();
+
+        let target_square = square.offset(0, multiplier).unwrap();
+        if !only_captures && self.pieces().get(&target_square).is_none() {
+            if promotes {
+                for r#type in [Queen, Rook, Bishop, Knight] {
+                    moves.push(Move::from_promotion(square, target_square, r#type));
                 }
-                Bishop => self.get_straight_moves(&mut moves, piece, &Directions::BISHOP, only_captures),
-                Rook => self.get_straight_moves(&mut moves, piece, &Directions::ROOK, only_captures),
-                Queen => self.get_straight_moves(&mut moves, piece, &Directions::QUEEN, only_captures),
-                King => {
-                    for (file, rank) in Directions::KING {
-                        if let Some(target_square) = square.offset(file, rank) {
-                            if let Some(target_piece) = self.pieces.get(&target_square) {
-                                if target_piece.color != self.turn {
-                                    moves.push(Move::from_capture(
-                                        square,
-                                        target_square,
-                                        target_piece.clone(),
-                                    ));
-                                }
-                            } else if !only_captures {
-                                moves.push(Move::from_normal(square, target_square));
-                            }
-                        }
-                    }
+            } else {
+                moves.push(Move::from_normal(square, target_square));
+
+                let jump_square = square.offset(0, 2 * multiplier);
+                if square.rank == self.turn.get_pawn_rank()
+                    && self.pieces().get(&jump_square.unwrap()).is_none()
+                {
+                    moves.push(Move::from_pawn_jump(square, jump_square.unwrap()));
                 }
             }
         }
 
-        let king_square = *self.kings.get(&self.turn).unwrap();
-        for square in Square::ALL {
-            if let Some(piece) = self.pieces.get(&square) {
-                if piece.color != self.turn {
-                    // This block of code runs where {piece} is every piece
-                    // on the enemy team
-
-                    let attack_lines = piece.get_attack_lines(square);
-                    if let Some(index) =
-                        attack_lines.iter().position(|al| al.contains(&king_square))
-                    {
-                        let attack_line = attack_lines
-                            .get(index)
-                            .expect("Invalid lines_with_king index");
-
-                        let mut blocking_pieces = 0;
-                        let mut resolving_squares = vec![square];
-
-                        for square in attack_line {
-                            let square = *square;
-
-                            if self.pieces.get(&square).is_some() {
-                                if square == king_square {
-                                    match blocking_pieces {
-                                        0 => {
-                                            in_check = true;
-
-                                            // Move that checks the King
-                                            moves.retain(|m| {
-                                                (m.from == king_square
-                                                    && !attack_line.contains(&m.to))
-                                                    || resolving_squares.contains(&m.to)
-													// Allow Enpassant where captured piece is checking the King
-                                                    || (m.r#type == Enpassant
-                                                        && resolving_squares.get(0).unwrap()
-                                                            == &square!(m.to.file m.from.rank))
-                                            });
-                                        }
-                                        1 => {
-                                            // Move that pins another piece
-                                            moves.retain(|m| {
-                                                !resolving_squares.contains(&m.from)
-                                                    || resolving_squares.contains(&m.to)
-                                            });
-                                        }
-                                        2 => {
-                                            // Enpassant that leaves the king in check
-                                            // This takes two pieces off the board
-                                            moves.retain(|m| {
-                                                m.r#type != Enpassant
-                                                    || m.from.rank != king_square.rank
-                                            })
-                                        }
-                                        _ => {}
-                                    }
-
-                                    break;
-                                } else {
-                                    blocking_pieces += 1;
-                                }
-                            }
-
-                            resolving_squares.push(square);
-                        }
-                    }
-
-                    // This block of code filter moves that regard our King
-                    // moving into a check
-                    for attack_line in &attack_lines {
-                        moves.retain(|m| {
-                            if m.from == king_square {
-                                for square in attack_line {
-                                    if self.pieces.get(square).is_some() {
-                                        return *square != m.to;
-                                    } else if *square == m.to {
-                                        return false;
-                                    }
-                                }
-                            }
-
-                            true
-                        });
-
-                        if unchecked_squares.iter().any(|s| attack_line.contains(s)) {
-                            for square in attack_line {
-                                if let Some(index) =
-                                    unchecked_squares.iter().position(|s| s == square)
-                                {
-                                    unchecked_squares.remove(index);
-                                    break;
-                                }
+        let attacks = PAWN_ATTACKS[self.turn as usize][square.index()];
+        for target_square in Squares(attacks & enemies) {
+            let target_piece = self.mailbox[target_square.index()].unwrap();
+            if promotes {
+                for r#type in [Queen, Rook, Bishop, Knight] {
+                    moves.push(Move::from_promotion_capture(
+                        square,
+                        target_square,
+                        target_piece,
+                        r#type,
+                    ));
+                }
+            } else {
+                moves.push(Move::from_capture(square, target_square, target_piece));
+            }
+        }
 
-                                if self.pieces.get(square).is_some() {
-                                    break;
-                                }
-                            }
-                        }
+        if let Some(enpassant_square) = self.enpassant_square {
+            if attacks & enpassant_square.bitboard() != 0
+                && enpassant_square.rank == self.turn.opposite().get_enpassant_rank()
+            {
+                let captured_square = square!(enpassant_square.file square.rank);
+                if let Some(captured) = self.pieces().get(&captured_square) {
+                    if captured.r#type == Pawn && captured.color != self.turn {
+                        moves.push(Move::from_enpassant(square, enpassant_square, *captured));
                     }
                 }
             }
         }
+    }
 
+    fn get_target_moves(
+        &self,
+        moves: &mut Vec<Move>,
+        square: Square,
+        targets: Bitboard,
+        only_captures: bool,
+    ) {
+        let targets = targets & !self.occupancy[self.turn as usize];
+        for target_square in Squares(targets) {
+            if let Some(target_piece) = self.pieces().get(&target_square) {
+                moves.push(Move::from_capture(square, target_square, *target_piece));
+            } else if !only_captures {
+                moves.push(Move::from_normal(square, target_square));
+            }
+        }
+    }
+
+    fn get_castling_moves(&self, moves: &mut Vec<Move>) {
+        let piece_rank = self.turn.get_piece_rank();
+        let opposite_color = self.turn.opposite();
+        let occupied = self.occupied();
         let CastlingRights {
             queenside,
             kingside,
-        } = *self.castling_rights.get(&self.turn).unwrap();
-        if !in_check {
-            if kingside
-                && [square!(F piece_rank), square!(G piece_rank)]
-                    .iter()
-                    .all(|s| self.pieces.get(s).is_none())
-                && unchecked_squares.contains(&square!(F piece_rank))
-                && unchecked_squares.contains(&square!(G piece_rank))
-            {
-                moves.push(Move::from_castle(
-                    square!(E piece_rank),
-                    square!(G piece_rank),
-                ));
-            }
+        } = self.castling_rights[self.turn as usize];
 
-            if queenside
-                && [
-                    square!(B piece_rank),
-                    square!(C piece_rank),
-                    square!(D piece_rank),
-                ]
+        if kingside
+            && [square!(F piece_rank), square!(G piece_rank)]
                 .iter()
-                .all(|s| self.pieces.get(s).is_none())
-                && unchecked_squares.contains(&square!(C piece_rank))
-                && unchecked_squares.contains(&square!(D piece_rank))
-            {
-                moves.push(Move::from_castle(
-                    square!(E piece_rank),
-                    square!(C piece_rank),
-                ));
-            }
+                .all(|s| occupied & s.bitboard() == 0 && !self.is_attacked(*s, opposite_color))
+        {
+            moves.push(Move::from_castle(
+                square!(E piece_rank),
+                square!(G piece_rank),
+            ));
         }
-        if only_captures {
-            moves.retain(|m| m.captured.is_some());
+
+        if queenside
+            && occupied & square!(B piece_rank).bitboard() == 0
+            && [square!(C piece_rank), square!(D piece_rank)]
+                .iter()
+                .all(|s| occupied & s.bitboard() == 0 && !self.is_attacked(*s, opposite_color))
+        {
+            moves.push(Move::from_castle(
+                square!(E piece_rank),
+                square!(C piece_rank),
+            ));
         }
-        (moves, in_check)
     }
 
-    fn get_straight_moves(&self, moves: &mut Vec<Move>, piece: &Piece, directions: &[(i8, i8)], only_captures: bool) {
-        let square = self.get_square(piece).unwrap();
-        for (file, rank) in directions {
-            let mut file_offset = *file;
-            let mut rank_offset = *rank;
-            while let Some(target_square) = square.offset(file_offset, rank_offset) {
-                if let Some(target_piece) = self.pieces.get(&target_square) {
-                    if target_piece.color != self.turn {
-                        moves.push(Move::from_capture(
-                            square,
-                            target_square,
-                            target_piece.clone(),
-                        ));
-                    }
-                    break;
-                } else if !only_captures {
-                    moves.push(Move::from_normal(square, target_square));
-                }
-                file_offset += file;
-                rank_offset += rank;
+    /// Checks that a pseudo-legal move does not leave our own king attacked.
+    /// Castling is fully validated while it is generated.
+    fn is_legal(&self, r#move: &Move) -> bool {
+        if r#move.r#type == Castle {
+            return true;
+        }
+
+        let mut occupied = self.occupied() & !r#move.from.bitboard() | r#move.to.bitboard();
+        let mut enemies = self.occupancy[self.turn.opposite() as usize];
+        if r#move.captured.is_some() {
+            let captured_square = if r#move.r#type == Enpassant {
+                square!(r#move.to.file r#move.from.rank)
+            } else {
+                r#move.to
+            };
+            enemies &= !captured_square.bitboard();
+            if r#move.r#type == Enpassant {
+                occupied &= !captured_square.bitboard();
             }
         }
+
+        let king_square = if self.bitboard(self.turn, King) & r#move.from.bitboard() != 0 {
+            r#move.to
+        } else {
+            self.king_square(self.turn)
+        };
+
+        self.attackers_to(king_square, occupied) & enemies == 0
     }
 }
@@ -1,4 +1,17 @@
-use crate::{piece_square_table::read_square_table, structs::*};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{
+    bitboard::Squares,
+    heuristics::Heuristics,
+    limits::{PruningMargins, SearchLimits},
+    piece_square_table::read_square_table,
+    score::{Score, MAX_PLY},
+    structs::*,
+    transposition_table::{pack_move, Bound, Entry, TranspositionTable},
+};
 
 const PAWN_VALUE: i32 = 100;
 const KNIGHT_VALUE: i32 = 300;
@@ -6,69 +19,605 @@ const BISHOP_VALUE: i32 = 300;
 const ROOK_VALUE: i32 = 500;
 const QUEEN_VALUE: i32 = 900;
 
-pub fn search(board: &mut Board, depth: usize, mut alpha: i32, beta: i32) -> (i32, Option<Move>) {
+/// Move ordering tiers: captures and promotions that don't lose material
+/// first, then killer moves, then the counter move, then the remaining quiet
+/// moves by history score, and losing captures last.
+const CAPTURE_BONUS: i32 = 1_000_000;
+const KILLER_BONUS: i32 = 900_000;
+const COUNTER_MOVE_BONUS: i32 = 700_000;
+
+/// Null-move pruning needs this much depth to be worth a reduced search.
+const NULL_MOVE_MIN_DEPTH: usize = 3;
+/// From this depth on a null-move cutoff is confirmed by a reduced normal
+/// search, in case the side to move is in zugzwang.
+const NULL_MOVE_VERIFICATION_DEPTH: usize = 8;
+
+/// Most plies a single path may be extended by, so extensions can't keep the
+/// search from ever reaching the horizon.
+const MAX_EXTENSIONS: usize = 16;
+/// Singular extensions need this much depth to pay for their verification search.
+const SINGULAR_MIN_DEPTH: usize = 8;
+
+/// Quiet moves from this index on are searched with reduced depth first.
+const LATE_MOVE_REDUCTION_INDEX: usize = 3;
+const LATE_MOVE_REDUCTION_MIN_DEPTH: usize = 3;
+/// Up to this depth, quiet moves past `3 + depth * depth` are not searched at all.
+const LATE_MOVE_PRUNING_DEPTH: usize = 3;
+
+/// From this depth on an iteration first searches a window this many
+/// centipawns either side of the previous score, widening it on every fail.
+const ASPIRATION_MIN_DEPTH: usize = 5;
+const ASPIRATION_WINDOW: i32 = 25;
+
+/// Helper threads skip some iterations so they are not all searching the
+/// same depth at once: helper `n` skips every other block of `SKIP_SIZE`
+/// depths, offset by `SKIP_PHASE`, with the patterns repeating after 20 helpers.
+const SKIP_SIZE: [usize; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [usize; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+/// Flags another thread raises to steer a running search.
+#[derive(Default)]
+pub struct SearchSignals {
+    /// Ends the search as soon as it notices.
+    pub stop: AtomicBool,
+    /// Set while pondering on the opponent's time, when the clock doesn't
+    /// count yet. Clearing it on a ponder hit puts the time limit in force.
+    pub pondering: AtomicBool,
+}
+
+/// Everything a search carries from node to node besides the board itself.
+pub struct SearchState<'a> {
+    pub tt: &'a TranspositionTable,
+    pub heuristics: Heuristics,
+    /// Triangular PV table: `pv[ply]` is the best line found from the node
+    /// currently being searched at `ply`.
+    pub pv: Vec<Vec<Move>>,
+    pub nodes: u64,
+    pub stopped: bool,
+    /// Set while a null-move cutoff is being verified, to stop that search
+    /// from relying on null moves itself.
+    verifying: bool,
+    /// Plies of extension used on the path to the current node.
+    extensions: usize,
+    /// A move the next call to `search` must skip, for singular extension checks.
+    excluded: Option<Move>,
+    signals: &'a SearchSignals,
+    /// Nodes visited by all threads of the search, added in batches of 1024.
+    node_count: &'a AtomicU64,
+    /// 0 for the main thread, which alone watches the limits, and 1 and up
+    /// for the helpers.
+    thread: usize,
+    start: Instant,
+    time_budget: Option<Duration>,
+    node_limit: Option<u64>,
+    check_limits: bool,
+    margins: PruningMargins,
+}
+
+/// The outcome of the last completed iteration.
+#[derive(Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// The line both sides are expected to play, starting with `best_move`.
+    pub pv: Vec<Move>,
+    pub score: Score,
+    /// Whether `score` is exact, or only a bound because the root search
+    /// failed outside its aspiration window.
+    pub bound: Bound,
+    pub depth: usize,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl<'a> SearchState<'a> {
+    pub fn new(
+        tt: &'a TranspositionTable,
+        limits: &SearchLimits,
+        turn: Color,
+        signals: &'a SearchSignals,
+        node_count: &'a AtomicU64,
+        thread: usize,
+    ) -> SearchState<'a> {
+        let main = thread == 0;
+        SearchState {
+            tt,
+            heuristics: Heuristics::new(),
+            pv: vec![vec![]; MAX_PLY as usize + 1],
+            nodes: 0,
+            stopped: false,
+            verifying: false,
+            extensions: 0,
+            excluded: None,
+            signals,
+            node_count,
+            thread,
+            start: Instant::now(),
+            time_budget: limits.time_budget(turn).filter(|_| main),
+            node_limit: limits.nodes.filter(|_| main),
+            check_limits: true,
+            margins: limits.margins.clone(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Nodes visited so far by all threads, counting this one exactly.
+    pub fn total_nodes(&self) -> u64 {
+        self.node_count.load(Ordering::Relaxed) + (self.nodes & 1023)
+    }
+
+    /// Whether `budget / divisor` of the thinking time has been used. The
+    /// clock only runs once pondering is over.
+    fn used_time(&self, divisor: u32) -> bool {
+        !self.signals.pondering.load(Ordering::Relaxed)
+            && self
+                .time_budget
+                .is_some_and(|budget| self.elapsed() >= budget / divisor)
+    }
+
+    /// Counts a node and reports whether the search has run out of nodes or
+    /// time, or was told to stop. The clock and stop flag are only read every
+    /// 1024 nodes.
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes & 1023 == 0 {
+            self.node_count.fetch_add(1024, Ordering::Relaxed);
+        }
+        if self.check_limits {
+            if self.node_limit.is_some_and(|limit| self.total_nodes() >= limit) {
+                self.stopped = true;
+            }
+            if self.nodes & 1023 == 0
+                && (self.signals.stop.load(Ordering::Relaxed) || self.used_time(1))
+            {
+                self.stopped = true;
+            }
+        }
+        self.stopped
+    }
+}
+
+/// Searches one ply deeper at a time until a limit is reached, keeping the
+/// result of the last iteration that finished. The first iteration always
+/// runs to completion so there is a move to play. `on_iteration` is called
+/// with the result of every completed iteration, and with a bound each time
+/// an aspiration window has to be widened.
+///
+/// With more than one thread this is Lazy SMP: helper threads search copies
+/// of the board, skipping different iterations, and share what they find
+/// through the transposition table. The main thread watches the limits and
+/// stops the helpers when it is done, and a helper's result is only taken
+/// when it completed a deeper iteration.
+pub fn iterative_deepening(
+    board: &Board,
+    tt: &TranspositionTable,
+    limits: &SearchLimits,
+    threads: usize,
+    signals: &SearchSignals,
+    mut on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let node_count = AtomicU64::new(0);
+    let helper_signals = SearchSignals::default();
+
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads.max(1))
+            .map(|thread| {
+                let mut board = board.clone();
+                let (node_count, helper_signals) = (&node_count, &helper_signals);
+                scope.spawn(move || {
+                    let mut state =
+                        SearchState::new(tt, limits, board.turn, helper_signals, node_count, thread);
+                    deepen(&mut board, &mut state, limits, |_| {})
+                })
+            })
+            .collect();
+
+        let mut board = board.clone();
+        let mut state = SearchState::new(tt, limits, board.turn, signals, &node_count, 0);
+        let mut result = deepen(&mut board, &mut state, limits, &mut on_iteration);
+
+        helper_signals.stop.store(true, Ordering::Relaxed);
+        let mut from_helper = false;
+        for helper in helpers {
+            let helper = helper.join().unwrap();
+            if helper.depth > result.depth && helper.best_move.is_some() {
+                result = helper;
+                from_helper = true;
+            }
+        }
+        result.nodes = node_count.load(Ordering::Relaxed);
+        result.elapsed = state.elapsed();
+        if from_helper {
+            on_iteration(&result);
+        }
+        result
+    })
+}
+
+/// The iterative deepening loop of a single thread.
+fn deepen(
+    board: &mut Board,
+    state: &mut SearchState,
+    limits: &SearchLimits,
+    mut on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let mut result = SearchResult {
+        best_move: None,
+        pv: vec![],
+        score: Score::DRAW,
+        bound: Bound::Exact,
+        depth: 0,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+    };
+
+    for depth in 1..=limits.max_depth() {
+        if state.thread > 0 {
+            let skip = (state.thread - 1) % SKIP_SIZE.len();
+            if (depth + SKIP_PHASE[skip]) / SKIP_SIZE[skip] % 2 == 1 {
+                continue;
+            }
+        }
+        state.check_limits = depth > 1;
+        let mut window = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if depth >= ASPIRATION_MIN_DEPTH && !result.score.is_mate() {
+            (Score(result.score.0 - window), Score(result.score.0 + window))
+        } else {
+            (-Score::INFINITY, Score::INFINITY)
+        };
+        let (score, best_move) = loop {
+            let (score, best_move) = search(board, state, depth, 0, alpha, beta);
+            if state.stopped || (alpha < score && score < beta) {
+                break (score, best_move);
+            }
+
+            // Only the side of the window that failed is widened, and once
+            // it is off by more than a queen it is opened all the way
+            let (bound, pv) = if score <= alpha {
+                alpha = if window > QUEEN_VALUE {
+                    -Score::INFINITY
+                } else {
+                    Score(alpha.0 - window)
+                };
+                (Bound::Upper, result.pv.clone())
+            } else {
+                beta = if window > QUEEN_VALUE {
+                    Score::INFINITY
+                } else {
+                    Score(beta.0 + window)
+                };
+                (Bound::Lower, best_move.into_iter().collect())
+            };
+            window *= 2;
+            on_iteration(&SearchResult {
+                best_move: pv.first().copied(),
+                pv,
+                score,
+                bound,
+                depth,
+                nodes: state.total_nodes(),
+                elapsed: state.elapsed(),
+            });
+        };
+        if state.stopped {
+            break;
+        }
+
+        result = SearchResult {
+            best_move: best_move.or(result.best_move),
+            pv: state.pv[0].clone(),
+            score,
+            bound: Bound::Exact,
+            depth,
+            nodes: state.total_nodes(),
+            elapsed: state.elapsed(),
+        };
+        on_iteration(&result);
+
+        // Another iteration takes several times longer than this one, so there
+        // is no point starting it with less than half the budget left
+        if state.used_time(2) {
+            break;
+        }
+    }
+
+    // Hand over the nodes that did not make up a whole batch
+    state.node_count.fetch_add(state.nodes & 1023, Ordering::Relaxed);
+    result.nodes = state.nodes;
+    result.elapsed = state.elapsed();
+    result
+}
+
+/// Principal variation search: the first move of a node is searched with the
+/// full window and the rest with a null window around alpha, which is only
+/// widened again for a move that turns out to be better.
+pub fn search(
+    board: &mut Board,
+    state: &mut SearchState,
+    depth: usize,
+    ply: usize,
+    mut alpha: Score,
+    beta: Score,
+) -> (Score, Option<Move>) {
+    if let Some(pv) = state.pv.get_mut(ply) {
+        pv.clear();
+    }
+    let excluded = state.excluded.take();
     if depth == 0 {
-        return (search_all_captures(board, alpha, beta), None);
+        return (search_all_captures(board, state, ply, alpha, beta), None);
+    }
+    if state.visit() {
+        return (Score::DRAW, None);
+    }
+    // Inside the tree a single repetition is enough to call the position a draw
+    if ply > 0 && (board.repetitions() > 0 || board.has_insufficient_material()) {
+        return (Score::DRAW, None);
+    }
+    let pv_node = beta.0 - alpha.0 > 1;
+    // A search without one of the moves must not share table entries with the full one
+    let entry = state.tt.probe(board.hash).filter(|_| excluded.is_none());
+    // The root must always come back with a move, and PV nodes must build
+    // their line, so neither stops at the table
+    if let Some(entry) = entry {
+        if ply > 0 && !pv_node && entry.depth as usize >= depth {
+            if let Some(score) = entry.cutoff(alpha, beta, ply) {
+                return (score, None);
+            }
+        }
     }
     let (moves, in_check) = board.get_moves(false);
-    if moves.len() == 0 {
+    if moves.is_empty() {
         if in_check {
-            return (i32::MIN, None);
+            return (Score::mated_in(ply), None);
         }
-        return (0, None);
+        return (Score::DRAW, None);
     }
-    let mut best_move = moves[0].clone();
-    let moves = order_moves(board, moves);
-    for r#move in moves {
-        let castling_rights = board.castling_rights.clone();
-        let enpassant_square = board.enpassant_square;
-        let halfmove_clock = board.halfmove_clock;
+    // Only now, since a mate on the hundredth half-move still wins
+    if ply > 0 && board.halfmove_clock >= 100 {
+        return (Score::DRAW, None);
+    }
+    let moves: Vec<Move> = moves.into_iter().filter(|m| Some(*m) != excluded).collect();
+    if moves.is_empty() {
+        return (alpha, None);
+    }
+    let previous = board.history.last().map(|entry| entry.r#move);
+    // Evaluation based pruning needs a quiet enough position and real bounds to compare with
+    let prunable =
+        !pv_node && !in_check && excluded.is_none() && !alpha.is_mate() && !beta.is_mate();
+    let static_evaluation = Score(if prunable { eval(board) } else { 0 });
+    if prunable {
+        let reverse_futility = margin_at(&state.margins.reverse_futility, depth);
+        if reverse_futility.is_some_and(|margin| static_evaluation.0 - margin >= beta.0) {
+            return (beta, None);
+        }
+        // Razoring: only captures could save the node, so let quiescence decide
+        let razoring = margin_at(&state.margins.razoring, depth);
+        if razoring.is_some_and(|margin| static_evaluation.0 + margin < alpha.0) {
+            let score = search_all_captures(board, state, ply, Score(alpha.0 - 1), alpha);
+            if state.stopped {
+                return (Score::DRAW, None);
+            }
+            if score < alpha {
+                return (alpha, None);
+            }
+        }
+    }
+    if prunable
+        && depth >= NULL_MOVE_MIN_DEPTH
+        && static_evaluation >= beta
+        && null_move_allowed(board, state, previous)
+    {
+        if let Some(score) = null_move_search(board, state, depth, ply, beta, static_evaluation) {
+            return (score, None);
+        }
+    }
+    let futility = margin_at(&state.margins.futility, depth);
+    let futile = prunable && futility.is_some_and(|margin| static_evaluation.0 + margin <= alpha.0);
+    let previous = previous.filter(|m| m.r#type != Null);
+    let original_alpha = alpha;
+    let hash_move = entry.and_then(|e| e.best_move());
+    let moves = order_moves(board, moves, hash_move, &state.heuristics, ply, previous);
+    let singular = ply > 0
+        && excluded.is_none()
+        && depth >= SINGULAR_MIN_DEPTH
+        && state.extensions < MAX_EXTENSIONS
+        && Some(pack_move(&moves[0])) == hash_move
+        && entry.is_some_and(|entry| is_singular(board, state, &entry, moves[0], depth, ply));
+    if state.stopped {
+        return (Score::DRAW, None);
+    }
+    let mut best_move = moves[0];
+    for (index, r#move) in moves.into_iter().enumerate() {
+        // Quiet moves this far down the ordering rarely turn out best
+        let late_quiet =
+            index > 0 && r#move.is_quiet() && !in_check && !board.gives_check(&r#move);
+        if late_quiet
+            && !pv_node
+            && depth <= LATE_MOVE_PRUNING_DEPTH
+            && index >= 3 + depth * depth
+            && !alpha.is_mate()
+        {
+            continue;
+        }
+        // Near the horizon a quiet move can't make up for a large deficit
+        if late_quiet && futile {
+            continue;
+        }
 
-        board.execute(r#move.clone());
+        board.execute(r#move);
+
+        // Forced replies, recaptures at PV nodes and a singular hash move
+        // are worth a ply more, within the budget for this path
+        let recapture = previous.is_some_and(|p| p.is_capture() && p.to == r#move.to);
+        let extension = (state.extensions < MAX_EXTENSIONS
+            && ((index == 0 && singular)
+                || board.in_check()
+                || (pv_node && recapture && r#move.is_capture()))) as usize;
+        let new_depth = depth - 1 + extension;
+        state.extensions += extension;
 
-        let evaluation = search(board, depth - 1, beta.wrapping_neg(), alpha.wrapping_neg())
-            .0
-            .wrapping_neg();
+        let mut evaluation;
+        if index == 0 {
+            evaluation = -search(board, state, new_depth, ply + 1, -beta, -alpha).0;
+        } else {
+            let null_window = -Score(alpha.0 + 1);
+            let mut reduction = 0;
+            if late_quiet
+                && depth >= LATE_MOVE_REDUCTION_MIN_DEPTH
+                && index >= LATE_MOVE_REDUCTION_INDEX
+            {
+                reduction = late_move_reduction(depth, index)
+                    .saturating_sub(pv_node as usize)
+                    .min(depth - 2);
+            }
+            evaluation =
+                -search(board, state, new_depth - reduction, ply + 1, null_window, -alpha).0;
+            // A reduced move that beats alpha gets its full depth back
+            if evaluation > alpha && reduction > 0 {
+                evaluation = -search(board, state, new_depth, ply + 1, null_window, -alpha).0;
+            }
+            if evaluation > alpha && evaluation < beta {
+                evaluation = -search(board, state, new_depth, ply + 1, -beta, -alpha).0;
+            }
+        }
 
-        board.undo(castling_rights, enpassant_square, halfmove_clock);
+        state.extensions -= extension;
+        board.undo();
+        if state.stopped {
+            return (Score::DRAW, None);
+        }
         if evaluation >= beta {
-            return (beta, None);
+            if r#move.is_quiet() {
+                state.heuristics.record_cutoff(board.turn, r#move, previous, ply, depth);
+            }
+            if excluded.is_none() {
+                state.tt.store(board.hash, depth, ply, Bound::Lower, beta, Some(&r#move));
+            }
+            return (beta, Some(r#move));
         }
         if evaluation > alpha {
             alpha = evaluation;
             best_move = r#move;
+            update_pv(&mut state.pv, ply, r#move);
         }
-        alpha = alpha.max(evaluation)
     }
 
+    if excluded.is_none() {
+        if alpha > original_alpha {
+            state.tt.store(board.hash, depth, ply, Bound::Exact, alpha, Some(&best_move));
+        } else {
+            state.tt.store(board.hash, depth, ply, Bound::Upper, alpha, None);
+        }
+    }
     (alpha, Some(best_move))
 }
+
+/// Whether the hash move is the only good move here: every other move fails
+/// low against a bound a little below the hash move's stored score, in a
+/// search of half the depth.
+fn is_singular(
+    board: &mut Board,
+    state: &mut SearchState,
+    entry: &Entry,
+    hash_move: Move,
+    depth: usize,
+    ply: usize,
+) -> bool {
+    let score = entry.score(ply);
+    if entry.bound == Bound::Upper || entry.depth as usize + 3 < depth || score.is_mate() {
+        return false;
+    }
+
+    let singular_beta = Score(score.0 - 2 * depth as i32);
+    state.excluded = Some(hash_move);
+    let score = search(board, state, depth / 2, ply, Score(singular_beta.0 - 1), singular_beta).0;
+    state.excluded = None;
+    score < singular_beta
+}
+
+/// How many plies to take off a late quiet move, growing with the logarithm
+/// of both the remaining depth and the move's place in the ordering.
+fn late_move_reduction(depth: usize, index: usize) -> usize {
+    static TABLE: OnceLock<[[u8; 64]; 64]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [[0; 64]; 64];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+                *reduction = (0.75 + (depth as f64).ln() * (index as f64).ln() / 2.25) as u8;
+            }
+        }
+        table
+    });
+    table[depth.min(63)][index.min(63)] as usize
+}
+
+/// Whether the side to move may pass. Not twice in a row, not while a null
+/// move is being verified, and not with only pawns left, where zugzwang is common.
+fn null_move_allowed(board: &Board, state: &SearchState, previous: Option<Move>) -> bool {
+    let pieces = board.bitboard(board.turn, Knight)
+        | board.bitboard(board.turn, Bishop)
+        | board.bitboard(board.turn, Rook)
+        | board.bitboard(board.turn, Queen);
+    !state.verifying && pieces != 0 && previous.is_none_or(|m| m.r#type != Null)
+}
+
+/// The margin one of the [`PruningMargins`] lists, which start at depth 1,
+/// gives for `depth`.
+fn margin_at(margins: &[Option<i32>], depth: usize) -> Option<i32> {
+    margins.get(depth.checked_sub(1)?).copied().flatten()
+}
+
+/// Lets the opponent move twice: if a reduced search still fails high, the
+/// position is good enough to cut off. The reduction grows with depth and
+/// with how far the static evaluation is above beta.
+fn null_move_search(
+    board: &mut Board,
+    state: &mut SearchState,
+    depth: usize,
+    ply: usize,
+    beta: Score,
+    static_evaluation: Score,
+) -> Option<Score> {
+    let reduction = 3 + depth / 4 + ((static_evaluation.0 - beta.0) / 200).min(2) as usize;
+    let null_depth = depth.saturating_sub(reduction);
+
+    board.make_null_move();
+    let score = -search(board, state, null_depth, ply + 1, -beta, -Score(beta.0 - 1)).0;
+    board.unmake_null_move();
+    if state.stopped || score < beta {
+        return None;
+    }
+
+    if depth >= NULL_MOVE_VERIFICATION_DEPTH {
+        state.verifying = true;
+        let verification = search(board, state, null_depth, ply, Score(beta.0 - 1), beta).0;
+        state.verifying = false;
+        if state.stopped || verification < beta {
+            return None;
+        }
+    }
+    Some(beta)
+}
+
+/// Makes `r#move` followed by the child's line the line of the node at `ply`.
+fn update_pv(pv: &mut [Vec<Move>], ply: usize, r#move: Move) {
+    if ply + 1 >= pv.len() {
+        return;
+    }
+    let (parent, child) = pv.split_at_mut(ply + 1);
+    let line = &mut parent[ply];
+    line.clear();
+    line.push(r#move);
+    line.extend_from_slice(&child[0]);
+}
 fn eval(board: &Board) -> i32 {
     let perspective = if board.turn == Color::White { 1 } else { -1 };
     let (mut white_eval, white_pawns) = count_material(board, Color::White);
     let (mut black_eval, black_pawns) = count_material(board, Color::Black);
-    let kings: Vec<&Square> = board
-        .pieces
-        .iter()
-        .filter_map(|(square, piece)| {
-            if piece.r#type == PieceType::King {
-                Some(square)
-            } else {
-                None
-            }
-        })
-        .collect();
-    let mut white_king_square = &Square {rank: Rank::_1, file: File::A};
-    let mut black_king_square = &Square {rank: Rank::_1, file: File::A};
-
-    for &square in &kings {
-        if let Some(piece) = board.pieces.get(square) {
-            match piece.color {
-                Color::White => white_king_square = square,
-                Color::Black => black_king_square = square,
-            }
-        }
-    }
+    let white_king_square = &board.king_square(Color::White);
+    let black_king_square = &board.king_square(Color::Black);
     let white_endgame_weight = endgame_phase_weight(white_eval - white_pawns * PAWN_VALUE);
     let black_endgame_weight = endgame_phase_weight(black_eval - black_pawns * PAWN_VALUE);
     white_eval += force_king_to_corner_endgame_eval(
@@ -88,9 +637,9 @@ fn eval(board: &Board) -> i32 {
 }
 fn evalute_piece_square_tables(board: &Board, color: Color, endgame_weight: f32) -> i32 {
     let mut value = 0;
-    for (square, piece) in &board.pieces {
-        if piece.color == color {
-            value += read_square_table(piece.r#type, square, piece.color, endgame_weight);
+    for r#type in [Pawn, Knight, Bishop, Rook, Queen, King] {
+        for square in Squares(board.bitboard(color, r#type)) {
+            value += read_square_table(r#type, &square, color, endgame_weight);
         }
     }
     value
@@ -102,16 +651,10 @@ fn endgame_phase_weight(material_without_pawns: i32) -> f32 {
 }
 fn count_material(board: &Board, color: Color) -> (i32, i32) {
     let mut material = 0;
-    let mut num_pawns = 0;
-    for (_, piece) in &board.pieces {
-        if piece.color != color {
-            continue;
-        }
-        material += get_piece_value(&piece.r#type);
-        if let PieceType::Pawn = piece.r#type {
-            num_pawns += 1;
-        }
+    for r#type in [Pawn, Knight, Bishop, Rook, Queen] {
+        material += board.bitboard(color, r#type).count_ones() as i32 * get_piece_value(&r#type);
     }
+    let num_pawns = board.bitboard(color, Pawn).count_ones() as i32;
     (material, num_pawns)
 }
 fn force_king_to_corner_endgame_eval(
@@ -135,33 +678,91 @@ fn force_king_to_corner_endgame_eval(
     evaluation += 14 - dist_between_kings;
     (evaluation as f32 * 10.0 * endgame_weight).round() as i32
 }
-fn order_moves(board: &Board, moves: Vec<Move>) -> Vec<Move> {
+fn order_moves(
+    board: &Board,
+    moves: Vec<Move>,
+    hash_move: Option<u16>,
+    heuristics: &Heuristics,
+    ply: usize,
+    previous: Option<Move>,
+) -> Vec<Move> {
+    let scores = score_moves(board, &moves, hash_move, heuristics, ply, previous);
+    sort_moves(moves, scores)
+}
+/// How promising each move looks for ordering. Only captures that lose
+/// material score below zero.
+fn score_moves(
+    board: &Board,
+    moves: &[Move],
+    hash_move: Option<u16>,
+    heuristics: &Heuristics,
+    ply: usize,
+    previous: Option<Move>,
+) -> Vec<i32> {
+    let killers = heuristics.killers(ply);
+    let counter_move = heuristics.counter_move(previous);
     let mut scores = vec![];
-    for r#move in &moves {
-        let mut score_guess = 0;
-        let move_piece_type = board.pieces.get(&r#move.from);
-        let capture_piece_type = board.pieces.get(&r#move.to);
+    for r#move in moves {
+        // the best move found last time this position was searched goes first
+        if Some(pack_move(r#move)) == hash_move {
+            scores.push(i32::MAX);
+            continue;
+        }
+        if r#move.is_quiet() {
+            // quiet moves that caused cutoffs elsewhere are likely to do it again
+            scores.push(if killers[0] == Some(*r#move) {
+                KILLER_BONUS
+            } else if killers[1] == Some(*r#move) {
+                KILLER_BONUS - 100_000
+            } else if counter_move == Some(*r#move) {
+                COUNTER_MOVE_BONUS
+            } else {
+                heuristics.history(board.turn, r#move)
+            });
+            continue;
+        }
+        // captures that lose material in the exchange come after every quiet move
+        if let Some(see) = exchange_loss(board, r#move) {
+            scores.push(see - CAPTURE_BONUS);
+            continue;
+        }
+        let mut score_guess = CAPTURE_BONUS;
+        let move_piece_type = board.pieces().get(&r#move.from);
         // prioritise capturing opponent's most valuable pieces with our least valuable pieces
-        if let Some(_) = capture_piece_type {
-            score_guess = 10 * get_piece_value(&capture_piece_type.unwrap().r#type)
+        if let Some(captured) = r#move.captured {
+            score_guess += 10 * get_piece_value(&captured.r#type)
                 - get_piece_value(&move_piece_type.unwrap().r#type);
         }
         //promoting a pawn is probably good
-        if r#move.promotion.is_some() {
-            score_guess += get_piece_value(&r#move.promotion.unwrap());
+        if let Some(promotion) = r#move.promotion {
+            score_guess += get_piece_value(&promotion);
         }
         scores.push(score_guess);
     }
-    sort_moves(moves, scores)
+    scores
+}
+/// The static exchange evaluation of a capture or promotion that gives away
+/// more than it wins, or `None` if it doesn't. Taking a piece at least as
+/// valuable as the capturer never does, which saves the exchange evaluation
+/// for most captures.
+fn exchange_loss(board: &Board, r#move: &Move) -> Option<i32> {
+    if let Some(captured) = r#move.captured {
+        let capturer = board.pieces().get(&r#move.from).unwrap().r#type;
+        if r#move.promotion.is_none() && get_piece_value(&captured.r#type) >= get_piece_value(&capturer) {
+            return None;
+        }
+    }
+    let see = board.see(r#move);
+    (see < 0).then_some(see)
 }
 fn sort_moves(moves: Vec<Move>, scores: Vec<i32>) -> Vec<Move> {
     let mut zipped: Vec<_> = moves.into_iter().zip(scores).collect();
-    zipped.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by score (descending)
+    zipped.sort_by_key(|z| std::cmp::Reverse(z.1)); // Sort by score (descending)
 
     // Unzip the structs back
     zipped.into_iter().map(|(s, _)| s).collect()
 }
-fn get_piece_value(piece: &PieceType) -> i32 {
+pub fn get_piece_value(piece: &PieceType) -> i32 {
     match piece {
         Pawn => PAWN_VALUE,
         Knight => KNIGHT_VALUE,
@@ -171,26 +772,150 @@ fn get_piece_value(piece: &PieceType) -> i32 {
         King => 0,
     }
 }
-fn search_all_captures(board: &mut Board, mut alpha: i32, beta: i32) -> i32 {
-    let mut evaluation = eval(board);
+fn search_all_captures(
+    board: &mut Board,
+    state: &mut SearchState,
+    ply: usize,
+    mut alpha: Score,
+    beta: Score,
+) -> Score {
+    if state.visit() {
+        return Score::DRAW;
+    }
+    let entry = state.tt.probe(board.hash);
+    if let Some(score) = entry.and_then(|e| e.cutoff(alpha, beta, ply)) {
+        return score;
+    }
+    let mut evaluation = Score(eval(board));
     if evaluation >= beta {
         return beta;
     }
+    let original_alpha = alpha;
+    let mut best_move = None;
     alpha = alpha.max(evaluation);
     let capture_moves = board.get_moves(true).0;
-    let capture_moves = order_moves(board, capture_moves);
-    for r#move in capture_moves {
-        let castling_rights = board.castling_rights.clone();
-        let enpassant_square = board.enpassant_square;
-        let halfmove_clock = board.halfmove_clock;
+    let hash_move = entry.and_then(|e| e.best_move());
+    let scores = score_moves(board, &capture_moves, hash_move, &state.heuristics, ply, None);
+    // a capture that loses material can't raise alpha above the static evaluation
+    let (capture_moves, scores): (Vec<Move>, Vec<i32>) = capture_moves
+        .into_iter()
+        .zip(scores)
+        .filter(|&(_, score)| score >= 0)
+        .unzip();
+    for r#move in sort_moves(capture_moves, scores) {
         board.execute(r#move);
-        evaluation =
-            search_all_captures(board, beta.wrapping_neg(), alpha.wrapping_neg()).wrapping_neg();
-        board.undo(castling_rights, enpassant_square, halfmove_clock);
+        evaluation = -search_all_captures(board, state, ply + 1, -beta, -alpha);
+        board.undo();
+        if state.stopped {
+            return Score::DRAW;
+        }
         if evaluation >= beta {
+            state.tt.store(board.hash, 0, ply, Bound::Lower, beta, Some(&r#move));
             return beta;
         }
-        alpha = alpha.max(evaluation);
+        if evaluation > alpha {
+            alpha = evaluation;
+            best_move = Some(r#move);
+        }
     }
+    let bound = if alpha > original_alpha && best_move.is_some() {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    };
+    state.tt.store(board.hash, 0, ply, bound, alpha, best_move.as_ref());
     alpha
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+
+    use super::{iterative_deepening, null_move_allowed, SearchResult, SearchSignals, SearchState};
+    use crate::limits::{PruningMargins, SearchLimits};
+    use crate::score::Score;
+    use crate::structs::*;
+    use crate::transposition_table::TranspositionTable;
+
+    fn search(fen: &str, limits: &SearchLimits) -> SearchResult {
+        let board = Board::from_fen(fen.to_string()).unwrap();
+        let tt = TranspositionTable::new(1);
+        iterative_deepening(&board, &tt, limits, 1, &SearchSignals::default(), |_| {})
+    }
+
+    /// Of the mates on the board the search picks the fastest, and a mate in
+    /// one scores higher than a mate in two.
+    #[test]
+    fn faster_mates_score_higher() {
+        let mate_in_one = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &SearchLimits::depth(4));
+        assert_eq!(mate_in_one.best_move.unwrap().to_uci(), "a1a8");
+        assert_eq!(mate_in_one.score, Score(Score::MATE.0 - 1));
+
+        let mate_in_two = search(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 0 1",
+            &SearchLimits::depth(4),
+        );
+        assert_eq!(mate_in_two.score, Score(Score::MATE.0 - 3));
+        assert!(mate_in_one.score > mate_in_two.score);
+    }
+
+    /// A standard null-move zugzwang test: Black's king and rook are tied up,
+    /// and only Rf1 keeps it that way until Black runs out of pawn moves.
+    #[test]
+    fn null_move_zugzwang() {
+        let result = search("8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1", &SearchLimits::depth(8));
+        assert_eq!(result.best_move.unwrap().to_uci(), "e1f1");
+    }
+
+    #[test]
+    fn null_move_safeguards() {
+        let tt = TranspositionTable::new(1);
+        let limits = SearchLimits::default();
+        let signals = SearchSignals::default();
+        let node_count = AtomicU64::new(0);
+        let mut state = SearchState::new(&tt, &limits, White, &signals, &node_count, 0);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K1N1 w - - 0 1".to_string()).unwrap();
+        assert!(null_move_allowed(&board, &state, None));
+        // Never twice in a row
+        board.make_null_move();
+        let previous = board.history.last().map(|entry| entry.r#move);
+        assert!(!null_move_allowed(&board, &state, previous));
+        board.unmake_null_move();
+        // Nor in a verification search
+        state.verifying = true;
+        assert!(!null_move_allowed(&board, &state, None));
+        state.verifying = false;
+        // Nor with only pawns left, where zugzwang is common
+        let pawns = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1".to_string()).unwrap();
+        assert!(!null_move_allowed(&pawns, &state, None));
+    }
+
+    /// A pawn ending is searched without null moves, so none can reach the line played.
+    #[test]
+    fn no_null_moves_in_pawn_endings() {
+        for fen in ["8/3k4/8/3p4/3P4/3K4/8/8 w - - 0 1", "8/8/1p6/1P1k4/8/3K4/8/8 b - - 0 1"] {
+            let result = search(fen, &SearchLimits::depth(10));
+            assert!(!result.pv.is_empty());
+            assert!(result.pv.iter().all(|r#move| r#move.r#type != Null), "{}", fen);
+        }
+    }
+
+    /// Pruning on the static evaluation only saves time: without any the
+    /// search finds the same mate.
+    #[test]
+    fn pruning_margins_keep_the_mate() {
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 0 1";
+        let none = PruningMargins {
+            reverse_futility: vec![],
+            razoring: vec![],
+            futility: vec![],
+        };
+        for margins in [PruningMargins::default(), none] {
+            let result = search(fen, &SearchLimits { margins, ..SearchLimits::depth(5) });
+            assert_eq!(result.score.mate_moves(), Some(2));
+            let pv: Vec<String> = result.pv.iter().map(|r#move| r#move.to_uci()).collect();
+            assert_eq!(pv, ["d5f6", "g7f6", "c4f7"]);
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use chess_engine::square;
+use chess_engine_macros::square;
 use indexmap::{indexmap, IndexMap};
 
 use crate::structs::*;
 use crate::*;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
 #[derive(Debug)]
 pub enum FenError {
     InvalidFen,
@@ -35,7 +38,7 @@ impl Board {
         let halfmove_clock = Board::parse_halfmove_clock(halfmove_clock)?;
         let fullmove_number = Board::parse_fullmove_number(fullmove_number)?;
 
-        for (color, castling_rights) in &castling_rights {
+        for (color, castling_rights) in [White, Black].iter().zip(&castling_rights) {
             let king = pieces.get(
                 &(if *color == White {
                     square!(E1)
@@ -103,31 +106,74 @@ impl Board {
             return Err(FenError::HalfMoveClock);
         }
 
-        let kings = indexmap! {
-            White => pieces.iter().find_map(|(s, p)| if p.r#type == King && p.color == White { Some(*s) } else {None} ).unwrap(),
-            Black => pieces.iter().find_map(|(s, p)| if p.r#type == King && p.color == Black { Some(*s) } else {None} ).unwrap(),
-        };
+        let mut board = Board::empty();
+        for (square, piece) in pieces {
+            board.put_piece(square, piece);
+        }
+        board.turn = active_color;
+        board.castling_rights = castling_rights;
+        board.enpassant_square = enpassant_square;
+        board.halfmove_clock = halfmove_clock;
+        board.fullmove_number = fullmove_number;
+        board.hash = board.compute_hash();
 
-        let mut board = Board {
-            history: vec![],
-            pieces,
-            attack_lines: indexmap! {},
-            kings,
+        Ok(board)
+    }
 
-            turn: active_color,
-            castling_rights,
-            enpassant_square,
-            halfmove_clock,
-            fullmove_number,
-        };
+    pub fn to_fen(&self) -> String {
+        let mut piece_placements = String::new();
+        for rank_index in (0..8).rev() {
+            let mut empty_squares = 0;
+            for file_index in 0..8 {
+                match self.mailbox[rank_index * 8 + file_index] {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            piece_placements.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        piece_placements.push(piece.to_fen_char());
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                piece_placements.push_str(&empty_squares.to_string());
+            }
+            if rank_index > 0 {
+                piece_placements.push('/');
+            }
+        }
 
-        board.attack_lines = board
-            .pieces
-            .iter()
-            .map(|(s, p)| (*s, p.get_attack_lines(*s)))
-            .collect::<IndexMap<_, _>>();
+        let active_color = if self.turn == White { "w" } else { "b" };
 
-        Ok(board)
+        let mut castling_rights = String::new();
+        for (color, kingside, queenside) in [(White, 'K', 'Q'), (Black, 'k', 'q')] {
+            let rights = self.castling_rights[color as usize];
+            if rights.kingside {
+                castling_rights.push(kingside);
+            }
+            if rights.queenside {
+                castling_rights.push(queenside);
+            }
+        }
+        if castling_rights.is_empty() {
+            castling_rights.push('-');
+        }
+
+        let enpassant_square = match self.enpassant_square {
+            Some(square) => square.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            piece_placements,
+            active_color,
+            castling_rights,
+            enpassant_square,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
     }
 
     fn parse_piece_placements(text: &str) -> Result<IndexMap<Square, Piece>, FenError> {
@@ -205,8 +251,7 @@ impl Board {
         }
     }
 
-    fn parse_castling_rights(text: &str) -> Result<IndexMap<Color, CastlingRights>, FenError> {
-        let mut castling_rights = indexmap! {};
+    fn parse_castling_rights(text: &str) -> Result<[CastlingRights; 2], FenError> {
         let mut white_kingside = false;
         let mut white_queenside = false;
         let mut black_kingside = false;
@@ -223,10 +268,10 @@ impl Board {
             }
         }
 
-        castling_rights.insert(White, CastlingRights::new(white_kingside, white_queenside));
-        castling_rights.insert(Black, CastlingRights::new(black_kingside, black_queenside));
-
-        Ok(castling_rights)
+        Ok([
+            CastlingRights::new(white_kingside, white_queenside),
+            CastlingRights::new(black_kingside, black_queenside),
+        ])
     }
 
     fn parse_enpassant_square(text: &str) -> Result<Option<Square>, FenError> {
@@ -277,8 +322,26 @@ impl Board {
 
     fn parse_fullmove_number(text: &str) -> Result<u32, FenError> {
         match text.parse::<u32>() {
-            Ok(n) => Ok(n),
-            Err(_) => Err(FenError::FullMoveNumber),
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(FenError::FullMoveNumber),
+        }
+    }
+}
+
+impl Piece {
+    pub fn to_fen_char(self) -> char {
+        let char = match self.r#type {
+            Pawn => 'p',
+            Knight => 'n',
+            Bishop => 'b',
+            Rook => 'r',
+            Queen => 'q',
+            King => 'k',
+        };
+        if self.color == White {
+            char.to_ascii_uppercase()
+        } else {
+            char
         }
     }
 }
@@ -323,4 +386,72 @@ macro_rules! king {
     ($id:tt, $color:tt) => {
         $crate::structs::Piece::new($id, $crate::structs::King, $crate::structs::$color)
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::STARTING_FEN;
+    use crate::structs::Board;
+    use crate::test_positions::{for_each_position, KIWIPETE, POSITION_3, POSITION_4, POSITION_5};
+
+
+    fn play(fen: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_fen(fen.to_string()).unwrap();
+        for r#move in moves {
+            board.execute(board.parse_uci_move(r#move).unwrap());
+        }
+        board
+    }
+
+    /// Every position up to two plies from the perft positions survives a
+    /// trip through FEN.
+    #[test]
+    fn round_trip() {
+        for fen in [STARTING_FEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            for_each_position(&mut Board::from_fen(fen.to_string()).unwrap(), 2, &mut |board| {
+                let fen = board.to_fen();
+                assert!(Board::from_fen(fen.clone()).unwrap() == *board, "{}", fen);
+                assert_eq!(Board::from_fen(fen.clone()).unwrap().to_fen(), fen);
+            });
+        }
+    }
+
+    #[test]
+    fn move_counters() {
+        assert_eq!(
+            play(STARTING_FEN, &["e2e4"]).to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        assert_eq!(
+            play(STARTING_FEN, &["e2e4", "e7e5"]).to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        assert_eq!(
+            play(STARTING_FEN, &["g1f3", "g8f6", "f3g1"]).to_fen(),
+            "rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 3 2"
+        );
+        // A black move starting from a FEN also ends the full move
+        assert_eq!(
+            play("4k3/8/8/8/8/8/8/4K3 b - - 7 40", &["e8d8"]).to_fen(),
+            "3k4/8/8/8/8/8/8/4K3 w - - 8 41"
+        );
+    }
+
+    #[test]
+    fn en_passant() {
+        let board = play(STARTING_FEN, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3"
+        );
+        assert!(Board::from_fen(board.to_fen()).unwrap() == board);
+
+        // Taking en passant clears the square and resets the clock
+        let board = play(&board.to_fen(), &["e5d6"]);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/1pp1pppp/p2P4/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3"
+        );
+        assert!(Board::from_fen(board.to_fen()).unwrap() == board);
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::engine::{iterative_deepening, SearchResult, SearchSignals};
+use crate::fen::STARTING_FEN;
+use crate::limits::SearchLimits;
+use crate::structs::*;
+use crate::transposition_table::{TranspositionTable, DEFAULT_SIZE_MB};
+
+/// What a running search reports through the callback given to [`Engine::new`].
+pub enum SearchEvent {
+    /// An iteration completed, or an aspiration window failed and is being widened.
+    Progress(SearchResult),
+    /// The search is over and its best move should be played. Infinite and
+    /// pondering searches only finish once stopped or, for pondering, hit.
+    Finished(SearchResult),
+}
+
+/// Owns the position, the hash table and the search threads, and runs one
+/// search at a time in the background so the caller stays free to stop it.
+pub struct Engine {
+    board: Board,
+    tt: Arc<TranspositionTable>,
+    threads: usize,
+    signals: Arc<SearchSignals>,
+    search: Option<JoinHandle<()>>,
+    on_event: Arc<dyn Fn(SearchEvent) + Send + Sync>,
+}
+
+impl Engine {
+    pub fn new(on_event: impl Fn(SearchEvent) + Send + Sync + 'static) -> Engine {
+        Engine {
+            board: Board::from_fen(STARTING_FEN.to_string()).unwrap(),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_SIZE_MB)),
+            threads: 1,
+            signals: Arc::new(SearchSignals::default()),
+            search: None,
+            on_event: Arc::new(on_event),
+        }
+    }
+
+    /// Searches the current position until `limits` are reached or the
+    /// search is stopped, stopping any search still running first.
+    pub fn start(&mut self, limits: SearchLimits) {
+        self.stop();
+        self.signals.stop.store(false, Ordering::Relaxed);
+        self.signals.pondering.store(limits.ponder, Ordering::Relaxed);
+
+        let board = self.board.clone();
+        let tt = Arc::clone(&self.tt);
+        let signals = Arc::clone(&self.signals);
+        let on_event = Arc::clone(&self.on_event);
+        let threads = self.threads;
+
+        self.search = Some(thread::spawn(move || {
+            let result = iterative_deepening(&board, &tt, &limits, threads, &signals, |result| {
+                on_event(SearchEvent::Progress(result.clone()))
+            });
+
+            // The move of an infinite or pondering search must wait until
+            // the caller asks for it
+            while (limits.infinite || signals.pondering.load(Ordering::Relaxed))
+                && !signals.stop.load(Ordering::Relaxed)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+            on_event(SearchEvent::Finished(result));
+        }));
+    }
+
+    /// Stops the running search, if any, and waits until it has reported
+    /// its best move.
+    pub fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.signals.stop.store(true, Ordering::Relaxed);
+            search.join().unwrap();
+        }
+    }
+
+    /// The opponent played the expected move: a pondering search goes on as
+    /// a normal one, with its time limit counted from when it started.
+    pub fn ponderhit(&self) {
+        self.signals.pondering.store(false, Ordering::Relaxed);
+    }
+
+    pub fn set_position(&mut self, board: Board) {
+        self.stop();
+        self.board = board;
+    }
+
+    /// Forgets everything learned in the previous game.
+    pub fn new_game(&mut self) {
+        self.stop();
+        self.tt.clear();
+        self.board = Board::from_fen(STARTING_FEN.to_string()).unwrap();
+    }
+
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.stop();
+        self.tt = Arc::new(TranspositionTable::new(size_mb));
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::score::MAX_PLY;
+use crate::structs::*;
+
+/// History scores are halved once one of them passes this, so old cutoffs
+/// fade and the scores stay below the killer and counter-move bonuses.
+const HISTORY_LIMIT: i32 = 500_000;
+
+/// What the search has learned about quiet moves that caused beta cutoffs,
+/// used to try such moves early elsewhere in the tree.
+pub struct Heuristics {
+    /// Two most recent cutoff moves per ply.
+    killers: Vec<[Option<Move>; 2]>,
+    /// Butterfly table of cutoff counts indexed by `[Color][from][to]`,
+    /// weighted by the square of the remaining depth.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    /// The move that refuted each previous move, indexed by its `[from][to]`.
+    counter_moves: Box<[[Option<Move>; 64]; 64]>,
+}
+
+impl Heuristics {
+    pub fn new() -> Heuristics {
+        Heuristics {
+            killers: vec![[None; 2]; MAX_PLY as usize],
+            history: Box::new([[[0; 64]; 64]; 2]),
+            counter_moves: Box::new([[None; 64]; 64]),
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers.get(ply).copied().unwrap_or_default()
+    }
+
+    pub fn history(&self, color: Color, r#move: &Move) -> i32 {
+        self.history[color as usize][r#move.from.index()][r#move.to.index()]
+    }
+
+    pub fn counter_move(&self, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|p| self.counter_moves[p.from.index()][p.to.index()])
+    }
+
+    /// Records a quiet move that failed high at `ply` with `depth` plies left.
+    pub fn record_cutoff(
+        &mut self,
+        color: Color,
+        r#move: Move,
+        previous: Option<Move>,
+        ply: usize,
+        depth: usize,
+    ) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(r#move) {
+                killers[1] = killers[0];
+                killers[0] = Some(r#move);
+            }
+        }
+
+        let score = &mut self.history[color as usize][r#move.from.index()][r#move.to.index()];
+        *score += (depth * depth) as i32;
+        if *score > HISTORY_LIMIT {
+            for score in self.history.iter_mut().flatten().flatten() {
+                *score /= 2;
+            }
+        }
+
+        if let Some(previous) = previous {
+            self.counter_moves[previous.from.index()][previous.to.index()] = Some(r#move);
+        }
+    }
+}
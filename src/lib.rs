@@ -1,44 +1,33 @@
-use proc_macro::TokenStream;
+//! Board representation, move generation, notation and PGN, and an
+//! alpha-beta search that [`Engine`] runs in the background. Move generators
+//! can be checked against published node counts with [`Board::perft`] and
+//! [`Board::divide`].
 
-#[proc_macro]
-pub fn square(input: TokenStream) -> TokenStream {
-    let string = input.to_string();
+pub mod engine;
+pub mod fen;
+pub mod handle;
+pub mod limits;
+pub mod notation;
+pub mod outcome;
+pub mod pgn;
+pub mod score;
+pub mod structs;
+pub mod transposition_table;
 
-    if string.len() == 2 {
-        let mut chars = string.chars();
-        let file = chars.next().unwrap();
-        let rank = chars.next().unwrap();
+mod bitboard;
+mod board;
+mod heuristics;
+mod magic;
+mod perft;
+mod piece_square_table;
+mod play;
+mod see;
+#[cfg(test)]
+mod test_positions;
+mod zobrist;
 
-        format!(
-            r#"crate::structs::Square {{
-				file: crate::structs::File::{},
-				rank: crate::structs::Rank::_{}
-			}}"#,
-            file, rank
-        )
-        .parse()
-        .unwrap()
-    } else {
-        let mut chars = string.split_whitespace();
-        let mut file: String = chars.next().unwrap().into();
-        let mut rank: String = chars.next().unwrap().into();
-
-        if file.len() == 1 {
-            file = format!("crate::structs::File::{}", file);
-        }
-
-        if rank.len() == 1 {
-            rank = format!("crate::structs::Rank::_{}", rank);
-        }
-
-        format!(
-            r#"crate::structs::Square {{
-				file: {},
-				rank: {},
-			}}"#,
-            file, rank
-        )
-        .parse()
-        .unwrap()
-    }
-}
\ No newline at end of file
+pub use handle::{Engine, SearchEvent};
+pub use limits::{PruningMargins, SearchLimits};
+pub use magic::init;
+pub use pgn::{parse_pgn, Game};
+pub use structs::{Board, Move};
@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::structs::*;
+
+/// Deepest iteration the driver will start when no depth limit is given.
+pub const MAX_DEPTH: usize = 64;
+
+/// Moves assumed to be left in the game when the clock has no `movestogo`.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Thinking time when the clock of the side to move is missing.
+const FALLBACK_BUDGET: Duration = Duration::from_secs(1);
+
+/// Time kept in reserve so the engine never flags on overhead alone.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+/// When to stop searching, mirroring the limits a UCI `go` command can carry.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub nodes: Option<u64>,
+    pub infinite: bool,
+    /// Search on the opponent's time; the clock limits only apply after a ponder hit.
+    pub ponder: bool,
+    /// How far the static evaluation must be off the window to prune.
+    pub margins: PruningMargins,
+}
+
+/// Margins by remaining depth for pruning on the static evaluation, each
+/// list starting at depth 1. A node is given up as failing high when the
+/// evaluation is `reverse_futility` above beta, and handed to quiescence when
+/// it is `razoring` below alpha. Quiet moves are skipped when the evaluation
+/// plus `futility` can't reach alpha. A depth past the end of a list, or with
+/// `None` in it, is not pruned that way.
+#[derive(Clone, Debug)]
+pub struct PruningMargins {
+    pub reverse_futility: Vec<Option<i32>>,
+    pub razoring: Vec<Option<i32>>,
+    pub futility: Vec<Option<i32>>,
+}
+
+impl Default for PruningMargins {
+    fn default() -> PruningMargins {
+        PruningMargins {
+            reverse_futility: vec![Some(120), Some(240), Some(360)],
+            // Frontier nodes are left to futility pruning, which still tries checks
+            razoring: vec![None, Some(450), Some(600)],
+            // Frontier and pre-frontier nodes
+            futility: vec![Some(200), Some(500)],
+        }
+    }
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> SearchLimits {
+        SearchLimits {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH)
+    }
+
+    /// How long `turn` may think about this move, or `None` to think until
+    /// another limit is hit.
+    pub fn time_budget(&self, turn: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if let Some(movetime) = self.movetime {
+            return Some(movetime.saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1)));
+        }
+
+        let (time, increment) = match turn {
+            White => (self.wtime, self.winc.unwrap_or_default()),
+            Black => (self.btime, self.binc.unwrap_or_default()),
+        };
+        let time = match time {
+            Some(time) => time,
+            // Only the opponent's clock was sent, which still means the search is timed
+            None if self.wtime.is_some() || self.btime.is_some() => return Some(FALLBACK_BUDGET),
+            None => return None,
+        };
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = time / moves_to_go + increment * 3 / 4;
+        let available = time.saturating_sub(MOVE_OVERHEAD);
+        Some(budget.min(available).max(Duration::from_millis(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::SearchLimits;
+    use crate::structs::*;
+
+    #[test]
+    fn time_budget() {
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_secs(60)),
+            btime: Some(Duration::from_secs(30)),
+            binc: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        assert_eq!(limits.time_budget(White), Some(Duration::from_secs(2)));
+        assert_eq!(limits.time_budget(Black), Some(Duration::from_millis(2500)));
+
+        let movetime = SearchLimits::movetime(Duration::from_secs(1));
+        assert_eq!(movetime.time_budget(White), Some(Duration::from_millis(970)));
+        assert_eq!(SearchLimits::depth(5).time_budget(White), None);
+        let infinite = SearchLimits {
+            infinite: true,
+            ..limits
+        };
+        assert_eq!(infinite.time_budget(White), None);
+    }
+
+    /// A clock for the other side only must still keep the search timed.
+    #[test]
+    fn missing_clock() {
+        let limits = SearchLimits {
+            wtime: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        assert!(limits.time_budget(Black).is_some());
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::OnceLock;
+
+use crate::bitboard::Bitboard;
+use crate::structs::*;
+
+/// Fancy magic bitboards: every square owns a slice of one shared attack
+/// table, indexed by multiplying the relevant blockers with a magic number.
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct SliderTables {
+    bishops: Vec<Magic>,
+    rooks: Vec<Magic>,
+    attacks: Vec<Bitboard>,
+}
+
+static TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+fn tables() -> &'static SliderTables {
+    TABLES.get_or_init(|| {
+        let mut attacks = vec![];
+        let mut random = Xorshift(0x9E37_79B9_7F4A_7C15);
+        let bishops = find_magics(&Directions::BISHOP, &mut attacks, &mut random);
+        let rooks = find_magics(&Directions::ROOK, &mut attacks, &mut random);
+        SliderTables {
+            bishops,
+            rooks,
+            attacks,
+        }
+    })
+}
+
+/// Builds the slider tables up front so the first search doesn't pay for it.
+pub fn init() {
+    tables();
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.attacks[tables.bishops[square.index()].index(occupied)]
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.attacks[tables.rooks[square.index()].index(occupied)]
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(square, occupied) | rook_attacks(square, occupied)
+}
+
+fn find_magics(
+    directions: &[(i8, i8)],
+    attacks: &mut Vec<Bitboard>,
+    random: &mut Xorshift,
+) -> Vec<Magic> {
+    let mut magics = Vec::with_capacity(64);
+    for index in 0..64 {
+        let square = Square::from_index(index);
+        let mask = relevant_blockers(square, directions);
+        let bits = mask.count_ones();
+
+        let mut occupancies = Vec::with_capacity(1 << bits);
+        let mut references = Vec::with_capacity(1 << bits);
+        let mut subset: Bitboard = 0;
+        loop {
+            occupancies.push(subset);
+            references.push(slider_attacks(square, subset, directions));
+            subset = subset.wrapping_sub(mask) & mask;
+            if subset == 0 {
+                break;
+            }
+        }
+
+        let shift = 64 - bits;
+        let mut table = vec![0; 1 << bits];
+        let mut used_in = vec![0u32; 1 << bits];
+        let mut attempt = 0;
+        let magic = 'search: loop {
+            let magic = random.sparse();
+            if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+                continue;
+            }
+
+            attempt += 1;
+            for (occupied, reference) in occupancies.iter().zip(&references) {
+                let slot = (occupied.wrapping_mul(magic) >> shift) as usize;
+                if used_in[slot] != attempt {
+                    used_in[slot] = attempt;
+                    table[slot] = *reference;
+                } else if table[slot] != *reference {
+                    continue 'search;
+                }
+            }
+            break magic;
+        };
+
+        magics.push(Magic {
+            mask,
+            magic,
+            shift,
+            offset: attacks.len(),
+        });
+        attacks.extend(table);
+    }
+    magics
+}
+
+/// Squares whose occupancy can change a slider's attacks. The last square of
+/// each ray never blocks anything behind it, so it is left out of the mask.
+fn relevant_blockers(square: Square, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = 0;
+    for (file, rank) in directions {
+        let mut current_square = square;
+        while let Some(target_square) = current_square.offset(*file, *rank) {
+            if target_square.offset(*file, *rank).is_none() {
+                break;
+            }
+            mask |= target_square.bitboard();
+            current_square = target_square;
+        }
+    }
+    mask
+}
+
+/// Walks every direction from `square` until the edge of the board or the
+/// first occupied square, which is included in the result.
+fn slider_attacks(square: Square, occupied: Bitboard, directions: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = 0;
+    for (file, rank) in directions {
+        let mut current_square = square;
+        while let Some(target_square) = current_square.offset(*file, *rank) {
+            attacks |= target_square.bitboard();
+            if occupied & target_square.bitboard() != 0 {
+                break;
+            }
+            current_square = target_square;
+        }
+    }
+    attacks
+}
+
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Magic candidates work best with few bits set.
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
@@ -1,145 +1,209 @@
 use std::io::stdin;
+use std::time::{Duration, Instant};
 
-use engine::search;
-use structs::{Board, File, PieceType::*, Rank, Square};
+use chess_engine::engine::{iterative_deepening, SearchSignals};
+use chess_engine::fen::STARTING_FEN;
+use chess_engine::notation::MoveParseError;
+use chess_engine::pgn;
+use chess_engine::transposition_table::{TranspositionTable, DEFAULT_SIZE_MB};
+use chess_engine::{Board, SearchLimits};
 
-mod board;
-mod engine;
-mod fen;
-mod piece_square_table;
-mod play;
-mod structs;
+mod uci;
 
 fn main() {
-    let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-    let mut board = Board::from_fen(fen.to_string()).unwrap();
+    chess_engine::init();
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("uci") => {
+            uci::uci_loop(None);
+            return;
+        }
+        Some("perft") => {
+            let depth = args.get(2).and_then(|depth| depth.parse().ok());
+            let fen = args.get(3..).map(|fen| fen.join(" ")).unwrap_or_default();
+            match depth {
+                Some(depth) => print_divide(depth, if fen.is_empty() { STARTING_FEN } else { &fen }),
+                None => println!("Usage: chess_engine perft <depth> [fen]"),
+            }
+            return;
+        }
+        Some("pgn") => {
+            match args.get(2) {
+                Some(path) => print_pgn_summary(path),
+                None => println!("Usage: chess_engine pgn <file>"),
+            }
+            return;
+        }
+        _ => {}
+    }
+    let mut board = Board::from_fen(STARTING_FEN.to_string()).unwrap();
     game_loop(&mut board);
 }
 
 fn game_loop(board: &mut Board) {
-    let mut depth = String::new();
-    println!("Welcome to Chess! Please enter the depth for the AI to evaluate at.");
-    stdin().read_line(&mut depth).expect("Failed to read input");
-    let depth: usize = depth.trim().parse().unwrap();
+    let mut limit = String::new();
+    println!("Welcome to Chess! Please enter the depth for the AI to evaluate at, or a time per move in seconds (e.g., 5s).");
+    stdin().read_line(&mut limit).expect("Failed to read input");
+    // GUIs start engines without arguments and open with "uci"
+    if limit.trim() == "uci" {
+        uci::uci_loop(Some(limit.trim()));
+        return;
+    }
+    let limits = match limit.trim().strip_suffix('s') {
+        Some(seconds) => SearchLimits::movetime(Duration::from_secs_f64(seconds.parse().unwrap())),
+        None => SearchLimits::depth(limit.trim().parse().unwrap()),
+    };
+    let tt = TranspositionTable::new(DEFAULT_SIZE_MB);
+    // Engine evaluation of each ply played so far, kept for PGN export
+    let mut evals: Vec<Option<String>> = vec![];
     board.print_board();
-    loop {
+    'game: loop {
         println!("Current turn: {:?}", board.fullmove_number);
+        println!("FEN: {}", board.to_fen());
 
-        // Generate all valid moves for the current turn
-        let (moves, in_check) = board.get_moves(false);
-        if moves.len() == 0 {
-            if in_check {
-                println!("Checkmate! Game over!");
-            }
-            println!("Stalemate! Game over!");
+        if let Some(result) = board.outcome() {
+            println!("{}", result);
+            offer_save(board, &evals);
             return;
         }
 
-        let mut from;
-        let mut to;
-        let mut move_is_valid = false;
-        while !move_is_valid {
-            from = String::new();
-            to = String::new();
-            println!("Enter the 'from' position (e.g., e2): ");
-            stdin().read_line(&mut from).expect("Failed to read input");
-            let from = from.trim();
-
-            println!("Enter the 'to' position (e.g., e4): ");
-            stdin().read_line(&mut to).expect("Failed to read input");
-            let to = to.trim();
+        loop {
+            let mut input = String::new();
+            println!("Enter your move (e.g., e4, Nf3, O-O or e8=N), or 'undo' to take back your last move, or 'save <file>' to save the game as PGN: ");
+            if stdin().read_line(&mut input).expect("Failed to read input") == 0 {
+                return;
+            }
+            let input = input.trim();
 
-            // Convert chess notation to board index
-            let from_idx = chess_notation_to_square(from);
-            let to_idx = chess_notation_to_square(to);
+            if input == "undo" {
+                if board.takeback() {
+                    evals.truncate(board.history.len());
+                    board.print_board();
+                    continue 'game;
+                }
+                println!("There is no move to take back.");
+                continue;
+            }
+            if let Some(path) = input.strip_prefix("save ") {
+                save_game(board, &evals, path.trim());
+                continue;
+            }
 
-            match (from_idx, to_idx) {
-                (Some(from), Some(to)) => {
-                    let move_played = moves.iter().find(|e| e.from == from && e.to == to);
-                    match move_played {
-                        Some(move_exists) => {
-                            move_is_valid = true;
-                            board.execute(move_exists.clone());
-                        }
-                        _ => {
-                            println!("Cannot play that move. Try again.");
-                        }
-                    }
+            match board.parse_san(input) {
+                Ok(r#move) => {
+                    board.execute(r#move);
+                    evals.push(None);
+                    break;
+                }
+                Err(MoveParseError::IllegalMove) => println!("Cannot play that move. Try again."),
+                Err(MoveParseError::AmbiguousMove) => {
+                    println!("More than one piece can make that move. Try again.")
                 }
-                _ => println!("Invalid chess notation. Try again."),
+                Err(MoveParseError::InvalidNotation) => println!("Invalid chess notation. Try again."),
             }
         }
         board.print_board();
+        if let Some(result) = board.outcome() {
+            println!("{}", result);
+            offer_save(board, &evals);
+            return;
+        }
         println!();
         println!("The AI is thinking...");
         println!();
-        let best_move = search(board, depth, i32::MIN, i32::MAX).1.unwrap();
-        board.execute(best_move.clone());
+        let result = iterative_deepening(board, &tt, &limits, 1, &SearchSignals::default(), |_| {});
+        let best_move = result.best_move.unwrap();
+        let san = board.to_san(&best_move);
+        let line = board.line_to_san(&result.pv).join(" ");
+        evals.push(Some(pgn::eval_comment(result.score, board.turn, result.depth)));
+        board.execute(best_move);
         board.print_board();
         println!(
-            "The AI played a move: {} to {}",
-            best_move.from, best_move.to
+            "The AI played a move: {} (depth {}, score {}, {} nodes)",
+            san, result.depth, result.score, result.nodes
         );
+        println!("Expected line: {}", line);
     }
 }
 
-fn chess_notation_to_square(pos: &str) -> Option<Square> {
-    // Ensure the string has exactly 2 characters
-    if pos.len() != 2 {
-        return None;
+/// Asks for a file to save the finished game to.
+fn offer_save(board: &Board, evals: &[Option<String>]) {
+    println!("Enter a file name to save the game as PGN, or press enter to quit: ");
+    let mut path = String::new();
+    stdin().read_line(&mut path).expect("Failed to read input");
+    if !path.trim().is_empty() {
+        save_game(board, evals, path.trim());
     }
-    // Extract the file and rank characters
-    let file_char = pos.chars().nth(0).unwrap().to_ascii_lowercase();
-    let rank_char = pos.chars().nth(1).unwrap();
-
-    // Convert the file character to the File enum
-    let file = match file_char {
-        'a' => File::A,
-        'b' => File::B,
-        'c' => File::C,
-        'd' => File::D,
-        'e' => File::E,
-        'f' => File::F,
-        'g' => File::G,
-        'h' => File::H,
-        _ => return None,
-    };
-
-    // Convert the rank character to the Rank enum
-    let rank = match rank_char {
-        '1' => Rank::_1,
-        '2' => Rank::_2,
-        '3' => Rank::_3,
-        '4' => Rank::_4,
-        '5' => Rank::_5,
-        '6' => Rank::_6,
-        '7' => Rank::_7,
-        '8' => Rank::_8,
-        _ => return None,
-    };
-
-    // Return the parsed Square
-    Some(Square { file, rank })
 }
-fn _perft(board: &mut Board, depth: usize) -> usize {
-    if depth == 0 {
-        return 1;
+
+fn save_game(board: &Board, evals: &[Option<String>], path: &str) {
+    let mut game = pgn::Game::from_board(board);
+    game.tags.insert("Event".to_string(), "Casual game".to_string());
+    game.tags.insert("White".to_string(), "Player".to_string());
+    game.tags.insert("Black".to_string(), "chess_engine".to_string());
+    for (game_move, eval) in game.moves.iter_mut().zip(evals) {
+        game_move.comment = eval.clone();
     }
 
-    let mut count = 0;
+    match std::fs::write(path, game.to_pgn()) {
+        Ok(()) => println!("Saved the game to {}", path),
+        Err(error) => println!("Could not save the game to {}: {}", path, error),
+    }
+}
 
-    for r#move in board.get_moves(false).0 {
-        let castling_rights = board.castling_rights.clone();
-        let enpassant_square = board.enpassant_square;
-        let halfmove_clock = board.halfmove_clock;
+/// Prints the node count below each root move and the total, the format
+/// move generator debugging tools compare against.
+fn print_divide(depth: usize, fen: &str) {
+    let mut board = match Board::from_fen(fen.to_string()) {
+        Ok(board) => board,
+        Err(error) => {
+            println!("Invalid FEN {}: {:?}", fen, error);
+            return;
+        }
+    };
 
-        board.execute(r#move);
+    let start = Instant::now();
+    let mut total = 0;
+    for (r#move, nodes) in board.divide(depth) {
+        println!("{}: {}", r#move, nodes);
+        total += nodes;
+    }
+    let elapsed = start.elapsed();
+    println!();
+    println!("Nodes searched: {}", total);
+    println!(
+        "Time: {} ms ({} nodes per second)",
+        elapsed.as_millis(),
+        (total as f64 / elapsed.as_secs_f64().max(1e-9)) as u64
+    );
+}
 
-        let perft = _perft(board, depth - 1);
-        count += perft;
+/// Loads every game in a PGN file and prints where each one ends up.
+fn print_pgn_summary(path: &str) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("Could not read {}: {}", path, error);
+            return;
+        }
+    };
+    let games = match pgn::parse_pgn(&text) {
+        Ok(games) => games,
+        Err(error) => {
+            println!("{}: {}", path, error);
+            return;
+        }
+    };
 
-        board.undo(castling_rights, enpassant_square, halfmove_clock);
+    for game in games {
+        let tag = |name: &str| game.tags.get(name).map_or("?", |value| value.as_str());
+        println!(
+            "{} - {} {}, {} plies, final position {}",
+            tag("White"),
+            tag("Black"),
+            game.result,
+            game.moves.len(),
+            game.replay().to_fen()
+        );
     }
-
-    count
 }
@@ -0,0 +1,355 @@
+use std::fmt;
+
+use crate::structs::*;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum MoveParseError {
+    /// The text is not a move in the expected notation.
+    InvalidNotation,
+    /// The text is well formed but no legal move matches it.
+    IllegalMove,
+    /// More than one legal move matches the text.
+    AmbiguousMove,
+}
+
+impl Move {
+    /// Long algebraic notation as used by UCI, e.g. "e2e4", "e7e8n" or "e1g1",
+    /// and "0000" for a null move.
+    pub fn to_uci(self) -> String {
+        if self.r#type == Null {
+            return "0000".to_string();
+        }
+        match self.promotion {
+            Some(promotion) => format!("{}{}{}", self.from, self.to, promotion_char(promotion)),
+            None => format!("{}{}", self.from, self.to),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+impl Board {
+    /// Finds the legal move written in UCI notation, so the returned move
+    /// carries its real type, captured piece and promotion.
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, MoveParseError> {
+        let text = text.trim();
+        if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+            return Err(MoveParseError::InvalidNotation);
+        }
+        let from = parse_square(&text[0..2]).ok_or(MoveParseError::InvalidNotation)?;
+        let to = parse_square(&text[2..4]).ok_or(MoveParseError::InvalidNotation)?;
+        let promotion = match text[4..].chars().next() {
+            Some(c) => Some(promotion_type(c).ok_or(MoveParseError::InvalidNotation)?),
+            None => None,
+        };
+
+        self.get_moves(false)
+            .0
+            .into_iter()
+            .find(|m| m.from == from && m.to == to && m.promotion == promotion)
+            .ok_or(MoveParseError::IllegalMove)
+    }
+}
+
+impl Board {
+    /// Standard algebraic notation for a legal move in this position, e.g.
+    /// "Nbd7", "exd6", "O-O-O" or "e8=Q+".
+    pub fn to_san(&self, r#move: &Move) -> String {
+        let mut san = if r#move.r#type == Castle {
+            if r#move.to.file == File::G {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            let r#type = self.pieces().get(&r#move.from).unwrap().r#type;
+            let mut san = String::new();
+            if r#type == Pawn {
+                if r#move.captured.is_some() {
+                    san.push_str(&format!("{}x", r#move.from.file));
+                }
+            } else {
+                san.push(piece_char(r#type));
+                san.push_str(&self.disambiguation(r#move, r#type));
+                if r#move.captured.is_some() {
+                    san.push('x');
+                }
+            }
+            san.push_str(&r#move.to.to_string());
+            if let Some(promotion) = r#move.promotion {
+                san.push('=');
+                san.push(piece_char(promotion));
+            }
+            san
+        };
+
+        let mut board = self.clone();
+        board.execute(*r#move);
+        let (replies, in_check) = board.get_moves(false);
+        if in_check {
+            san.push(if replies.is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// SAN for a sequence of moves played one after another from this position.
+    pub fn line_to_san(&self, moves: &[Move]) -> Vec<String> {
+        let mut board = self.clone();
+        moves
+            .iter()
+            .map(|r#move| {
+                let san = board.to_san(r#move);
+                board.execute(*r#move);
+                san
+            })
+            .collect()
+    }
+
+    /// Finds the legal move written in standard algebraic notation. Check and
+    /// annotation suffixes, capture marks, "e.p.", "=" before a promotion and
+    /// zeros for castling are all optional, and long forms like "Ng1f3" work too.
+    pub fn parse_san(&self, text: &str) -> Result<Move, MoveParseError> {
+        let text = text.trim();
+        let text = text
+            .strip_suffix("e.p.")
+            .or_else(|| text.strip_suffix("ep"))
+            .unwrap_or(text);
+        let text: String = text
+            .chars()
+            .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | 'x' | ':' | '-' | '=' | '(' | ')' | ' '))
+            .collect();
+        let moves = self.get_moves(false).0;
+
+        let castle = match text.as_str() {
+            "OO" | "00" | "oo" => Some(File::G),
+            "OOO" | "000" | "ooo" => Some(File::C),
+            _ => None,
+        };
+        if let Some(file) = castle {
+            return moves
+                .into_iter()
+                .find(|m| m.r#type == Castle && m.to.file == file)
+                .ok_or(MoveParseError::IllegalMove);
+        }
+
+        if !text.is_ascii() || text.len() < 2 {
+            return Err(MoveParseError::InvalidNotation);
+        }
+        let mut text = text.as_str();
+        let r#type = match text.chars().next().and_then(piece_type) {
+            Some(r#type) => {
+                text = &text[1..];
+                r#type
+            }
+            None => Pawn,
+        };
+        let promotion = match text.chars().last().and_then(promotion_type) {
+            Some(promotion) if r#type == Pawn => {
+                text = &text[..text.len() - 1];
+                Some(promotion)
+            }
+            _ => None,
+        };
+        if text.len() < 2 || text.len() > 4 {
+            return Err(MoveParseError::InvalidNotation);
+        }
+        let to = parse_square(&text[text.len() - 2..]).ok_or(MoveParseError::InvalidNotation)?;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in text[..text.len() - 2].chars() {
+            match c {
+                'a'..='h' if from_file.is_none() => from_file = File::try_from(c as i8 - b'a' as i8).ok(),
+                '1'..='8' if from_rank.is_none() => from_rank = Rank::try_from(c as i8 - b'1' as i8).ok(),
+                _ => return Err(MoveParseError::InvalidNotation),
+            }
+        }
+
+        let mut candidates = moves.into_iter().filter(|m| {
+            m.to == to
+                && m.promotion == promotion
+                && self.pieces().get(&m.from).unwrap().r#type == r#type
+                && from_file.is_none_or(|file| m.from.file == file)
+                && from_rank.is_none_or(|rank| m.from.rank == rank)
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(r#move), None) => Ok(r#move),
+            (Some(_), Some(_)) => Err(MoveParseError::AmbiguousMove),
+            (None, _) => Err(MoveParseError::IllegalMove),
+        }
+    }
+
+    /// The origin file, rank or square needed to tell `r#move` apart from
+    /// other moves of the same piece type to the same square.
+    fn disambiguation(&self, r#move: &Move, r#type: PieceType) -> String {
+        let rivals: Vec<Square> = self
+            .get_moves(false)
+            .0
+            .into_iter()
+            .filter(|m| {
+                m.to == r#move.to
+                    && m.from != r#move.from
+                    && self.pieces().get(&m.from).unwrap().r#type == r#type
+            })
+            .map(|m| m.from)
+            .collect();
+
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals.iter().all(|s| s.file != r#move.from.file) {
+            r#move.from.file.to_string()
+        } else if rivals.iter().all(|s| s.rank != r#move.from.rank) {
+            r#move.from.rank.to_string()
+        } else {
+            r#move.from.to_string()
+        }
+    }
+}
+
+/// Parses a square such as "e4".
+pub fn parse_square(text: &str) -> Option<Square> {
+    let mut chars = text.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !file.is_ascii_lowercase() || !rank.is_ascii_digit() {
+        return None;
+    }
+
+    Some(Square {
+        file: File::try_from(file as i8 - b'a' as i8).ok()?,
+        rank: Rank::try_from(rank as i8 - b'1' as i8).ok()?,
+    })
+}
+
+fn promotion_char(r#type: PieceType) -> char {
+    match r#type {
+        Knight => 'n',
+        Bishop => 'b',
+        Rook => 'r',
+        _ => 'q',
+    }
+}
+
+fn piece_char(r#type: PieceType) -> char {
+    match r#type {
+        Pawn => 'P',
+        Knight => 'N',
+        Bishop => 'B',
+        Rook => 'R',
+        Queen => 'Q',
+        King => 'K',
+    }
+}
+
+/// Piece letters in SAN are upper case; a lower case "b" is a file.
+fn piece_type(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(Knight),
+        'B' => Some(Bishop),
+        'R' => Some(Rook),
+        'Q' => Some(Queen),
+        'K' => Some(King),
+        _ => None,
+    }
+}
+
+fn promotion_type(c: char) -> Option<PieceType> {
+    match c.to_ascii_lowercase() {
+        'n' => Some(Knight),
+        'b' => Some(Bishop),
+        'r' => Some(Rook),
+        'q' => Some(Queen),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MoveParseError;
+    use crate::fen::STARTING_FEN;
+    use crate::structs::*;
+    use crate::test_positions::{for_each_position, KIWIPETE, POSITION_4};
+
+    const PROMOTIONS: &str = "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen.to_string()).unwrap()
+    }
+
+    fn san(fen: &str, uci: &str) -> String {
+        let board = board(fen);
+        board.to_san(&board.parse_uci_move(uci).unwrap())
+    }
+
+    /// Every move up to two plies from each position reads back as itself
+    /// from both its SAN and its UCI notation.
+    #[test]
+    fn round_trip() {
+        for fen in [STARTING_FEN, KIWIPETE, POSITION_4, PROMOTIONS] {
+            for_each_position(&mut board(fen), 1, &mut |board| {
+                for r#move in board.get_moves(false).0 {
+                    let san = board.to_san(&r#move);
+                    assert!(board.parse_san(&san) == Ok(r#move), "{} in {}", san, board.to_fen());
+                    assert!(board.parse_uci_move(&r#move.to_uci()) == Ok(r#move), "{}", r#move);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn writes_san() {
+        assert_eq!(san(KIWIPETE, "e1g1"), "O-O");
+        assert_eq!(san(KIWIPETE, "e1c1"), "O-O-O");
+        assert_eq!(san(KIWIPETE, "e5f7"), "Nxf7");
+        assert_eq!(san(KIWIPETE, "d5e6"), "dxe6");
+        assert_eq!(san(KIWIPETE, "f3f6"), "Qxf6");
+        assert_eq!(san(PROMOTIONS, "a7b8q"), "axb8=Q+");
+        assert_eq!(san(PROMOTIONS, "a7a8n"), "a8=N");
+        assert_eq!(san("3k4/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+        assert_eq!(san("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2", "d8h4"), "Qh4#");
+        assert_eq!(san("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", "e5f6"), "exf6");
+    }
+
+    #[test]
+    fn disambiguates() {
+        // Knights on b8 and f6 both reach d7
+        assert_eq!(san("rn2k3/8/5n2/8/8/8/8/4K3 b - - 0 1", "b8d7"), "Nbd7");
+        // Rooks on a1 and a5 both reach a3
+        assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        // Queens on a1, c1 and a3 all reach b2
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "c1b2"), "Qcb2");
+    }
+
+    #[test]
+    fn reads_tolerant_san() {
+        let kiwipete = board(KIWIPETE);
+        let uci = |text: &str| kiwipete.parse_san(text).map(|m| m.to_uci());
+        assert_eq!(uci("0-0"), Ok("e1g1".to_string()));
+        assert_eq!(uci("o-o-o"), Ok("e1c1".to_string()));
+        assert_eq!(uci("Ne5xf7!?"), Ok("e5f7".to_string()));
+        assert_eq!(uci("Nf7+"), Ok("e5f7".to_string()));
+        assert_eq!(uci("d5:e6"), Ok("d5e6".to_string()));
+        assert_eq!(uci("Nd2"), Err(MoveParseError::IllegalMove));
+        assert_eq!(uci("Ng4"), Ok("e5g4".to_string()));
+        // Capture marks are optional, and ignored when wrong
+        assert_eq!(uci("Nb5"), Ok("c3b5".to_string()));
+        assert_eq!(uci("Nxb5"), Ok("c3b5".to_string()));
+        assert_eq!(uci("Nd7"), Ok("e5d7".to_string()));
+        assert_eq!(uci("Zz9"), Err(MoveParseError::InvalidNotation));
+
+        let promotions = board(PROMOTIONS);
+        assert_eq!(promotions.parse_san("axb8Q").map(|m| m.to_uci()), Ok("a7b8q".to_string()));
+        assert_eq!(promotions.parse_san("a8=(N)").map(|m| m.to_uci()), Ok("a7a8n".to_string()));
+
+        let en_passant = board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let r#move = en_passant.parse_san("exf6 e.p.").unwrap();
+        assert!(r#move.r#type == Enpassant);
+
+        let rooks = board("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1");
+        assert!(rooks.parse_san("Ra3") == Err(MoveParseError::AmbiguousMove));
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+
+use crate::bitboard::Bitboard;
+use crate::structs::*;
+
+/// Light squares of the board, used to tell bishops of different colors apart.
+const LIGHT_SQUARES: Bitboard = 0x55AA_55AA_55AA_55AA;
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum GameResult {
+    Checkmate { winner: Color },
+    Stalemate,
+    /// Claimable draw: 50 moves by each side without a capture or pawn move.
+    FiftyMoveRule,
+    /// Automatic draw after 75 moves by each side without a capture or pawn move.
+    SeventyFiveMoveRule,
+    /// Claimable draw: the same position occurred three times.
+    ThreefoldRepetition,
+    /// Automatic draw: the same position occurred five times.
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Checkmate { winner: White } => write!(f, "Checkmate! White wins!"),
+            GameResult::Checkmate { winner: Black } => write!(f, "Checkmate! Black wins!"),
+            GameResult::Stalemate => write!(f, "Stalemate! It's a draw!"),
+            GameResult::FiftyMoveRule => write!(f, "Draw by the fifty-move rule!"),
+            GameResult::SeventyFiveMoveRule => write!(f, "Draw by the seventy-five-move rule!"),
+            GameResult::ThreefoldRepetition => write!(f, "Draw by threefold repetition!"),
+            GameResult::FivefoldRepetition => write!(f, "Draw by fivefold repetition!"),
+            GameResult::InsufficientMaterial => write!(f, "Draw by insufficient material!"),
+        }
+    }
+}
+
+impl Board {
+    /// The result of the game if it is over, treating claimable draws as claimed.
+    pub fn outcome(&self) -> Option<GameResult> {
+        let (moves, in_check) = self.get_moves(false);
+        if moves.is_empty() {
+            return Some(if in_check {
+                GameResult::Checkmate {
+                    winner: self.turn.opposite(),
+                }
+            } else {
+                GameResult::Stalemate
+            });
+        }
+
+        let repetitions = self.repetitions();
+        if self.has_insufficient_material() {
+            Some(GameResult::InsufficientMaterial)
+        } else if repetitions >= 4 {
+            Some(GameResult::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(GameResult::SeventyFiveMoveRule)
+        } else if repetitions >= 2 {
+            Some(GameResult::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(GameResult::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// How many times the current position occurred before in the game.
+    /// Only positions since the last capture or pawn move can repeat, and
+    /// only every other one has the same side to move. A null move made by
+    /// the search ends the window too, since nothing repeats across a pass.
+    pub fn repetitions(&self) -> usize {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .take_while(|entry| entry.r#move.r#type != Null)
+            .skip(1)
+            .step_by(2)
+            .filter(|entry| entry.hash == self.hash)
+            .count()
+    }
+
+    /// Neither side can possibly checkmate: bare kings, a single minor piece,
+    /// or only bishops that all stand on the same color of square.
+    pub fn has_insufficient_material(&self) -> bool {
+        let mut heavy_pieces = 0;
+        for color in [White, Black] {
+            heavy_pieces |=
+                self.bitboard(color, Pawn) | self.bitboard(color, Rook) | self.bitboard(color, Queen);
+        }
+        if heavy_pieces != 0 {
+            return false;
+        }
+
+        let knights = self.bitboard(White, Knight) | self.bitboard(Black, Knight);
+        let bishops = self.bitboard(White, Bishop) | self.bitboard(Black, Bishop);
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameResult;
+    use crate::fen::STARTING_FEN;
+    use crate::structs::*;
+
+    fn play(fen: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_fen(fen.to_string()).unwrap();
+        for r#move in moves {
+            board.execute(board.parse_uci_move(r#move).unwrap());
+        }
+        board
+    }
+
+    fn outcome(fen: &str) -> Option<GameResult> {
+        play(fen, &[]).outcome()
+    }
+
+    #[test]
+    fn checkmate_and_stalemate() {
+        let board = play(STARTING_FEN, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert!(board.outcome() == Some(GameResult::Checkmate { winner: Black }));
+        assert!(outcome("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1") == Some(GameResult::Stalemate));
+        assert!(outcome(STARTING_FEN).is_none());
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_move_rules() {
+        let rook_ending = "4k3/8/8/8/8/8/8/R3K3 w - - 99 60";
+        assert!(outcome(rook_ending).is_none());
+        assert!(play(rook_ending, &["a1a2"]).outcome() == Some(GameResult::FiftyMoveRule));
+        // A pawn move starts the count again
+        let pawn_ending = "4k3/8/8/8/8/8/4P3/R3K3 w - - 99 60";
+        assert!(play(pawn_ending, &["a1a2"]).outcome() == Some(GameResult::FiftyMoveRule));
+        assert!(play(pawn_ending, &["e2e4"]).outcome().is_none());
+
+        let long_ending = "4k3/8/8/8/8/8/8/R3K3 w - - 149 85";
+        assert!(outcome(long_ending) == Some(GameResult::FiftyMoveRule));
+        assert!(play(long_ending, &["a1a2"]).outcome() == Some(GameResult::SeventyFiveMoveRule));
+
+        // Mate on the hundredth half-move still counts as mate
+        let board = play("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80", &["a1a8"]);
+        assert!(board.outcome() == Some(GameResult::Checkmate { winner: White }));
+    }
+
+    #[test]
+    fn repetitions() {
+        let cycle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves: Vec<&str> = cycle.iter().cycle().take(16).copied().collect();
+        let expected = [
+            None,
+            Some(GameResult::ThreefoldRepetition),
+            Some(GameResult::ThreefoldRepetition),
+            Some(GameResult::FivefoldRepetition),
+        ];
+        for (cycles, expected) in expected.into_iter().enumerate() {
+            let board = play(STARTING_FEN, &moves[..4 * (cycles + 1)]);
+            assert_eq!(board.repetitions(), cycles + 1);
+            assert!(board.outcome() == expected, "after {} cycles", cycles + 1);
+        }
+
+        // The same placement with the other side to move is a different position
+        let board = play(STARTING_FEN, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"]);
+        assert_eq!(board.repetitions(), 1);
+        let board = play(STARTING_FEN, &["g1f3", "g8f6", "f3g1", "f6g8", "b1c3"]);
+        assert_eq!(board.repetitions(), 0);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            // Bishops that all stand on dark squares
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",
+        ] {
+            assert!(outcome(fen) == Some(GameResult::InsufficientMaterial), "{}", fen);
+        }
+        for fen in [
+            // Bishops on squares of different colors
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        ] {
+            assert!(outcome(fen).is_none(), "{}", fen);
+        }
+    }
+}
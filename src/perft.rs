@@ -0,0 +1,90 @@
+use crate::structs::*;
+
+impl Board {
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&mut self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.get_moves(false).0;
+        // Every legal move is a leaf, so there is no need to play them
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut count = 0;
+        for r#move in moves {
+            self.execute(r#move);
+            count += self.perft(depth - 1);
+            self.undo();
+        }
+        count
+    }
+
+    /// [`Board::perft`] split by root move.
+    pub fn divide(&mut self, depth: usize) -> Vec<(Move, u64)> {
+        let mut counts = vec![];
+        for r#move in self.get_moves(false).0 {
+            self.execute(r#move);
+            counts.push((r#move, self.perft(depth.saturating_sub(1))));
+            self.undo();
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::STARTING_FEN;
+    use crate::structs::Board;
+    use crate::test_positions::{KIWIPETE, POSITION_3, POSITION_4, POSITION_5, POSITION_6};
+
+
+    /// Checks `fen` against the published node counts for depths 1, 2, ...
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let mut board = Board::from_fen(fen.to_string()).unwrap();
+        for (depth, &expected) in counts.iter().enumerate() {
+            assert_eq!(board.perft(depth + 1), expected, "{} at depth {}", fen, depth + 1);
+        }
+        assert!(board == Board::from_fen(fen.to_string()).unwrap());
+    }
+
+    #[test]
+    fn starting_position() {
+        assert_perft(STARTING_FEN, &[20, 400, 8_902, 197_281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2_039, 97_862]);
+    }
+
+    #[test]
+    fn position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2_812, 43_238, 674_624]);
+    }
+
+    #[test]
+    fn position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9_467, 422_333]);
+    }
+
+    #[test]
+    fn position_5() {
+        assert_perft(POSITION_5, &[44, 1_486, 62_379]);
+    }
+
+    #[test]
+    fn position_6() {
+        assert_perft(POSITION_6, &[46, 2_079, 89_890]);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let mut board = Board::from_fen(KIWIPETE.to_string()).unwrap();
+        let divide = board.divide(3);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 97_862);
+    }
+}
@@ -0,0 +1,704 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use indexmap::IndexMap;
+
+use crate::fen::STARTING_FEN;
+use crate::notation::MoveParseError;
+use crate::outcome::GameResult;
+use crate::score::Score;
+use crate::structs::*;
+
+/// Tags every exported game carries, in the order the standard asks for.
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+/// Export lines are kept below 80 characters.
+const MAX_LINE_LENGTH: usize = 79;
+
+/// The game termination marker that ends the movetext.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PgnResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// "*": the game is unfinished or the result is unknown.
+    Unknown,
+}
+
+impl PgnResult {
+    fn from_token(token: &str) -> Option<PgnResult> {
+        match token {
+            "1-0" => Some(PgnResult::WhiteWins),
+            "0-1" => Some(PgnResult::BlackWins),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PgnResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnResult::WhiteWins => write!(f, "1-0"),
+            PgnResult::BlackWins => write!(f, "0-1"),
+            PgnResult::Draw => write!(f, "1/2-1/2"),
+            PgnResult::Unknown => write!(f, "*"),
+        }
+    }
+}
+
+pub struct Game {
+    /// Tag pairs in the order they appeared, the seven-tag roster included.
+    pub tags: IndexMap<String, String>,
+    /// The position before the first move, taken from the FEN tag if there is one.
+    pub start: Board,
+    /// Comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<GameMove>,
+    pub result: PgnResult,
+}
+
+pub struct GameMove {
+    pub r#move: Move,
+    /// Numeric annotation glyphs, with "!", "?" and friends mapped to $1-$6.
+    pub nags: Vec<u8>,
+    /// Comment after the move.
+    pub comment: Option<String>,
+    /// Alternatives to this move, each played from the position before it.
+    pub variations: Vec<Variation>,
+}
+
+pub struct Variation {
+    /// Comment before the first move of the variation.
+    pub comment: Option<String>,
+    pub moves: Vec<GameMove>,
+}
+
+impl Game {
+    /// A record of the moves played on `board` from wherever its history starts.
+    pub fn from_board(board: &Board) -> Game {
+        let mut start = board.clone();
+        while start.undo().is_some() {}
+        let moves = board
+            .history
+            .iter()
+            .map(|entry| GameMove {
+                r#move: entry.r#move,
+                nags: vec![],
+                comment: None,
+                variations: vec![],
+            })
+            .collect();
+        let result = match board.outcome() {
+            Some(GameResult::Checkmate { winner: White }) => PgnResult::WhiteWins,
+            Some(GameResult::Checkmate { winner: Black }) => PgnResult::BlackWins,
+            Some(_) => PgnResult::Draw,
+            None => PgnResult::Unknown,
+        };
+
+        Game {
+            tags: IndexMap::new(),
+            start,
+            comment: None,
+            moves,
+            result,
+        }
+    }
+
+    /// Writes the game in PGN export format. Missing roster tags are filled
+    /// with "?", and a non-standard start position gets SetUp and FEN tags.
+    /// A brace comment can't contain "}", so any in a comment is written as ")".
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.to_string(),
+                "Date" => self.tags.get(name).cloned().unwrap_or("????.??.??".to_string()),
+                _ => self.tags.get(name).cloned().unwrap_or("?".to_string()),
+            };
+            pgn.push_str(&tag_pair(name, &value));
+        }
+        let start_fen = self.start.to_fen();
+        if start_fen != STARTING_FEN {
+            pgn.push_str(&tag_pair("SetUp", "1"));
+            pgn.push_str(&tag_pair("FEN", &start_fen));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                pgn.push_str(&tag_pair(name, value));
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = vec![];
+        if let Some(comment) = &self.comment {
+            tokens.push(comment_token(comment));
+        }
+        write_moves(&mut tokens, self.start.clone(), &self.moves);
+        tokens.push(self.result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push_str("\n\n");
+        pgn
+    }
+
+    /// Plays the main line onto the start position and returns the final position.
+    pub fn replay(&self) -> Board {
+        let mut board = self.start.clone();
+        for game_move in &self.moves {
+            board.execute(game_move.r#move);
+        }
+        board
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum PgnErrorKind {
+    UnexpectedCharacter(char),
+    UnexpectedEnd,
+    InvalidTag,
+    InvalidFen,
+    IllegalMove(String),
+    AmbiguousMove(String),
+    /// A variation or annotation glyph with no move before it.
+    MissingMove,
+    /// A result token inside a variation.
+    MisplacedResult,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            PgnErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            PgnErrorKind::UnexpectedEnd => write!(f, "unexpected end of input"),
+            PgnErrorKind::InvalidTag => write!(f, "invalid tag pair"),
+            PgnErrorKind::InvalidFen => write!(f, "invalid FEN tag"),
+            PgnErrorKind::IllegalMove(san) => write!(f, "illegal move {}", san),
+            PgnErrorKind::AmbiguousMove(san) => write!(f, "ambiguous move {}", san),
+            PgnErrorKind::MissingMove => write!(f, "annotation or variation before any move"),
+            PgnErrorKind::MisplacedResult => write!(f, "result inside a variation"),
+        }
+    }
+}
+
+/// An engine evaluation comment such as "+0.35/12" or "#-3/9", from White's
+/// point of view. `turn` is the side whose point of view `score` is from.
+pub fn eval_comment(score: Score, turn: Color, depth: usize) -> String {
+    let score = if turn == White { score } else { -score };
+    match score.mate_moves() {
+        Some(moves) => format!("#{}/{}", moves, depth),
+        None => format!("{:+.2}/{}", score.0 as f64 / 100.0, depth),
+    }
+}
+
+fn tag_pair(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A brace comment. PGN has no escape for "}" inside one, so each is
+/// replaced by ")" and the exported text differs from `comment` there.
+fn comment_token(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', ")"))
+}
+
+/// Appends the SAN movetext of `moves`, played from `board`, to `tokens`.
+/// Black's move number is repeated at the start of a line and after anything
+/// that interrupts the moves.
+fn write_moves(tokens: &mut Vec<String>, mut board: Board, moves: &[GameMove]) {
+    let mut needs_number = true;
+    for game_move in moves {
+        // The number shares a token with its move so a line break never splits them
+        let san = board.to_san(&game_move.r#move);
+        if board.turn == White {
+            tokens.push(format!("{}. {}", board.fullmove_number, san));
+        } else if needs_number {
+            tokens.push(format!("{}... {}", board.fullmove_number, san));
+        } else {
+            tokens.push(san);
+        }
+        tokens.extend(game_move.nags.iter().map(|nag| format!("${}", nag)));
+        needs_number = false;
+
+        if let Some(comment) = &game_move.comment {
+            tokens.push(comment_token(comment));
+            needs_number = true;
+        }
+        for variation in &game_move.variations {
+            let mut variation_tokens = vec![];
+            if let Some(comment) = &variation.comment {
+                variation_tokens.push(comment_token(comment));
+            }
+            write_moves(&mut variation_tokens, board.clone(), &variation.moves);
+            match variation_tokens.len() {
+                0 => tokens.push("()".to_string()),
+                n => {
+                    variation_tokens[0].insert(0, '(');
+                    variation_tokens[n - 1].push(')');
+                    tokens.extend(variation_tokens);
+                }
+            }
+            needs_number = true;
+        }
+
+        board.execute(game_move.r#move);
+    }
+}
+
+/// Parses every game in a PGN database.
+pub fn parse_pgn(text: &str) -> Result<Vec<Game>, PgnError> {
+    let mut reader = Reader::new(text);
+    let mut games = vec![];
+    loop {
+        reader.skip_whitespace();
+        if reader.peek().is_none() {
+            return Ok(games);
+        }
+        games.push(parse_game(&mut reader)?);
+    }
+}
+
+fn parse_game(reader: &mut Reader) -> Result<Game, PgnError> {
+    let mut tags = IndexMap::new();
+    let mut fen_position = (reader.line, reader.column);
+    loop {
+        reader.skip_whitespace();
+        if reader.peek() != Some('[') {
+            break;
+        }
+        let position = (reader.line, reader.column);
+        let (name, value) = parse_tag(reader)?;
+        if name == "FEN" {
+            fen_position = position;
+        }
+        tags.insert(name, value);
+    }
+
+    let fen = tags.get("FEN").map_or(STARTING_FEN, |fen| fen.as_str());
+    let start = Board::from_fen(fen.to_string()).map_err(|_| PgnError {
+        line: fen_position.0,
+        column: fen_position.1,
+        kind: PgnErrorKind::InvalidFen,
+    })?;
+
+    let (line, result) = parse_line(reader, start.clone(), 0)?;
+    let result = result
+        .or_else(|| tags.get("Result").and_then(|r| PgnResult::from_token(r)))
+        .unwrap_or(PgnResult::Unknown);
+    Ok(Game {
+        tags,
+        start,
+        comment: line.comment,
+        moves: line.moves,
+        result,
+    })
+}
+
+/// Parses `[Name "value"]`, with `\"` and `\\` escapes in the value.
+fn parse_tag(reader: &mut Reader) -> Result<(String, String), PgnError> {
+    reader.bump();
+    reader.skip_whitespace();
+    let mut name = String::new();
+    while let Some(c) = reader.peek().filter(|c| c.is_ascii_alphanumeric() || *c == '_') {
+        name.push(c);
+        reader.bump();
+    }
+    if name.is_empty() {
+        return Err(reader.error(PgnErrorKind::InvalidTag));
+    }
+
+    reader.skip_whitespace();
+    reader.expect('"')?;
+    let mut value = String::new();
+    loop {
+        let c = match reader.peek() {
+            Some('\n') => return Err(reader.error(PgnErrorKind::InvalidTag)),
+            Some(c) => c,
+            None => return Err(reader.error(PgnErrorKind::UnexpectedEnd)),
+        };
+        reader.bump();
+        match c {
+            '"' => break,
+            '\\' if reader.peek().is_some_and(|c| c != '\n') => {
+                value.push(reader.bump().unwrap())
+            }
+            _ => value.push(c),
+        }
+    }
+    reader.skip_whitespace();
+    reader.expect(']')?;
+    Ok((name, value))
+}
+
+/// Parses moves from `board` until the end of the game, or until the closing
+/// parenthesis when `depth` is inside a variation. Returns the result token
+/// that ended the main line, if any.
+fn parse_line(
+    reader: &mut Reader,
+    mut board: Board,
+    depth: usize,
+) -> Result<(Variation, Option<PgnResult>), PgnError> {
+    let mut line = Variation {
+        comment: None,
+        moves: vec![],
+    };
+
+    loop {
+        reader.skip_whitespace();
+        let (token_line, token_column) = (reader.line, reader.column);
+        let token_error = |kind| PgnError {
+            line: token_line,
+            column: token_column,
+            kind,
+        };
+
+        match reader.peek() {
+            None if depth == 0 => return Ok((line, None)),
+            None => return Err(reader.error(PgnErrorKind::UnexpectedEnd)),
+            // Tags of the next game right after movetext without a result
+            Some('[') if depth == 0 => return Ok((line, None)),
+            Some('{') => {
+                reader.bump();
+                let mut comment = String::new();
+                loop {
+                    match reader.bump() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(reader.error(PgnErrorKind::UnexpectedEnd)),
+                    }
+                }
+                add_comment(&mut line, &comment);
+            }
+            Some(';') => {
+                let mut comment = String::new();
+                reader.bump();
+                while let Some(c) = reader.peek().filter(|c| *c != '\n') {
+                    comment.push(c);
+                    reader.bump();
+                }
+                add_comment(&mut line, &comment);
+            }
+            Some('(') => {
+                reader.bump();
+                let Some(last) = line.moves.last_mut() else {
+                    return Err(token_error(PgnErrorKind::MissingMove));
+                };
+                let mut before = board.clone();
+                before.undo();
+                let (variation, _) = parse_line(reader, before, depth + 1)?;
+                last.variations.push(variation);
+            }
+            Some(')') if depth > 0 => {
+                reader.bump();
+                return Ok((line, None));
+            }
+            Some('$') => {
+                reader.bump();
+                let mut number = String::new();
+                while let Some(c) = reader.peek().filter(char::is_ascii_digit) {
+                    number.push(c);
+                    reader.bump();
+                }
+                let nag = number
+                    .parse()
+                    .map_err(|_| token_error(PgnErrorKind::UnexpectedCharacter('$')))?;
+                let Some(last) = line.moves.last_mut() else {
+                    return Err(token_error(PgnErrorKind::MissingMove));
+                };
+                last.nags.push(nag);
+            }
+            Some(c) if is_symbol_char(c) => {
+                let symbol = reader.read_symbol();
+                if let Some(result) = PgnResult::from_token(&symbol) {
+                    if depth > 0 {
+                        return Err(token_error(PgnErrorKind::MisplacedResult));
+                    }
+                    return Ok((line, Some(result)));
+                }
+                // Move numbers are only there for the reader
+                if symbol.chars().all(|c| c.is_ascii_digit()) {
+                    while reader.peek() == Some('.') {
+                        reader.bump();
+                    }
+                    continue;
+                }
+                if symbol == "e.p." || symbol == "ep" {
+                    continue;
+                }
+
+                let san = symbol.trim_end_matches(['!', '?']);
+                let nag = suffix_nag(&symbol[san.len()..]);
+                if san.is_empty() {
+                    let Some(last) = line.moves.last_mut() else {
+                        return Err(token_error(PgnErrorKind::MissingMove));
+                    };
+                    last.nags.extend(nag);
+                    continue;
+                }
+                let r#move = board.parse_san(san).map_err(|error| {
+                    token_error(match error {
+                        MoveParseError::AmbiguousMove => PgnErrorKind::AmbiguousMove(san.to_string()),
+                        _ => PgnErrorKind::IllegalMove(san.to_string()),
+                    })
+                })?;
+                board.execute(r#move);
+                line.moves.push(GameMove {
+                    r#move,
+                    nags: nag.into_iter().collect(),
+                    comment: None,
+                    variations: vec![],
+                });
+            }
+            Some(c) => return Err(token_error(PgnErrorKind::UnexpectedCharacter(c))),
+        }
+    }
+}
+
+/// Attaches a comment to the last move of the line, or to the line itself
+/// before its first move. Several comments in a row are joined.
+fn add_comment(line: &mut Variation, comment: &str) {
+    let comment = comment.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let target = match line.moves.last_mut() {
+        Some(last) => &mut last.comment,
+        None => &mut line.comment,
+    };
+    match target {
+        Some(existing) => {
+            existing.push(' ');
+            existing.push_str(&comment);
+        }
+        None => *target = Some(comment),
+    }
+}
+
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "+#=:-/*!?".contains(c)
+}
+
+/// Walks the input one character at a time, keeping track of the line and
+/// column for error messages.
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(text: &'a str) -> Reader<'a> {
+        Reader {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), PgnError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(PgnErrorKind::UnexpectedCharacter(c))),
+            None => Err(self.error(PgnErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+
+    /// Skips whitespace, a byte order mark and `%` escape lines.
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '%' && self.column == 1 {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else if c.is_whitespace() || c == '\u{feff}' {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Reads a move, move number or result. A move number stops at its first
+    /// dot so "1.e4" splits into "1" and "e4", while "e.p." stays whole.
+    fn read_symbol(&mut self) -> String {
+        let mut symbol = String::new();
+        while let Some(c) = self.peek() {
+            let number = !symbol.is_empty() && symbol.chars().all(|c| c.is_ascii_digit());
+            if !(is_symbol_char(c) || (c == '.' && !number)) {
+                break;
+            }
+            symbol.push(c);
+            self.bump();
+        }
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = r#"[Event "Casual Game"]
+[Site "Berlin GER"]
+[Date "1852.??.??"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Jean Dufresne"]
+[Result "1-0"]
+[ECO "C52"]
+
+{Evergreen game} 1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. b4 Bxb4 5. c3 Ba5 6. d4 exd4
+7. O-O d3 8. Qb3 Qf6 9. e5 Qg6 10. Re1 Nge7 11. Ba3 b5 $6 12. Qxb5 Rb8 13. Qa4
+Bb6 14. Nbd2 Bb7 15. Ne4 Qf5? (15... d5 16. exd6 e.p. (16. Nf6+ gxf6) 16... cxd6)
+16. Bxd3 Qh5 17. Nf6+ gxf6 18. exf6 Rg8 19. Rad1!! Qxf3 20. Rxe7+ Nxe7 21. Qxd7+
+Kxd7 22. Bf5+ Ke8 23. Bd7+ Kf8 24. Bxe7# 1-0
+"#;
+
+    #[test]
+    fn parses_a_game() {
+        let games = parse_pgn(GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+
+        let names: Vec<&str> = game.tags.keys().map(String::as_str).collect();
+        assert_eq!(names, ["Event", "Site", "Date", "Round", "White", "Black", "Result", "ECO"]);
+        assert_eq!(game.tags["White"], "Adolf Anderssen");
+        assert_eq!(game.result, PgnResult::WhiteWins);
+        assert_eq!(game.comment.as_deref(), Some("Evergreen game"));
+        assert_eq!(game.moves.len(), 47);
+
+        assert_eq!(game.moves[21].nags, [6]);
+        let fifteenth = &game.moves[29];
+        assert_eq!(fifteenth.nags, [2]);
+        assert_eq!(fifteenth.variations.len(), 1);
+        let variation = &fifteenth.variations[0].moves;
+        assert_eq!(variation.len(), 3);
+        assert!(variation[1].r#move.r#type == Enpassant);
+        assert_eq!(variation[1].variations[0].moves.len(), 2);
+        assert_eq!(game.moves[36].nags, [3]);
+
+        let end = game.replay();
+        assert!(end.outcome() == Some(GameResult::Checkmate { winner: White }));
+        assert_eq!(end.to_fen(), "1r3kr1/pbpBBp1p/1b3P2/8/8/2P2q2/P4PPP/3R2K1 b - - 0 24");
+    }
+
+    #[test]
+    fn parses_set_up_positions_and_several_games() {
+        let text = "[FEN \"4k3/P7/8/8/8/8/8/4K3 w - - 0 60\"]\n[SetUp \"1\"]\n\n60. a8=Q+ Kd7 *\n\n\
+                    1. d4 d5 ; queen's pawn\n2. c4 1/2-1/2\n";
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].start.to_fen(), "4k3/P7/8/8/8/8/8/4K3 w - - 0 60");
+        assert_eq!(games[0].replay().to_fen(), "Q7/3k4/8/8/8/8/8/4K3 w - - 1 61");
+        assert_eq!(games[0].result, PgnResult::Unknown);
+        assert_eq!(games[1].moves[1].comment.as_deref(), Some("queen's pawn"));
+        assert_eq!(games[1].result, PgnResult::Draw);
+    }
+
+    #[test]
+    fn exports_what_it_parses() {
+        let game = &parse_pgn(GAME).unwrap()[0];
+        let pgn = game.to_pgn();
+        assert!(pgn.starts_with("[Event \"Casual Game\"]\n"));
+        assert!(pgn.contains("[ECO \"C52\"]\n\n{Evergreen game} 1. e4 e5"));
+        assert!(pgn.contains("15. Ne4 Qf5 $2 (15... d5 16. exd6 (16. Nf6+ gxf6) 16... cxd6)"));
+        assert!(pgn.ends_with("24. Bxe7# 1-0\n\n"));
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let again = &parse_pgn(&pgn).unwrap()[0];
+        assert_eq!(again.to_pgn(), pgn);
+        assert!(again.replay() == game.replay());
+    }
+
+    #[test]
+    fn exports_set_up_positions_and_comments() {
+        let mut board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 b - - 0 60".to_string()).unwrap();
+        board.execute(board.parse_san("Kd7").unwrap());
+        let mut game = Game::from_board(&board);
+        game.tags.insert("White".to_string(), "Anna \"A\" B".to_string());
+        game.moves[0].comment = Some("closing } brace".to_string());
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[White \"Anna \\\"A\\\" B\"]\n"));
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/P7/8/8/8/8/8/4K3 b - - 0 60\"]\n"));
+        assert!(pgn.ends_with("\n60... Kd7 {closing ) brace} *\n\n"));
+        assert_eq!(parse_pgn(&pgn).unwrap()[0].tags["White"], "Anna \"A\" B");
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        let error = |text: &str| parse_pgn(text).err().unwrap();
+        assert_eq!(
+            error("[Event \"x\"]\n\n1. e4 e5\n2. Ke3 *"),
+            PgnError {
+                line: 4,
+                column: 4,
+                kind: PgnErrorKind::IllegalMove("Ke3".to_string()),
+            }
+        );
+        assert_eq!(error("1. e4 {unfinished").kind, PgnErrorKind::UnexpectedEnd);
+        assert_eq!(error("1. e4 (1. d4 1-0) *").kind, PgnErrorKind::MisplacedResult);
+        assert_eq!(error("($1) *").kind, PgnErrorKind::MissingMove);
+        assert_eq!(error("[Event \"x\n\"]").kind, PgnErrorKind::InvalidTag);
+        assert_eq!(error("[FEN \"8/8\"]\n*").kind, PgnErrorKind::InvalidFen);
+        assert_eq!(
+            error("1. d4 d5 2. Nf3 Nf6 3. Nd2 *").kind,
+            PgnErrorKind::AmbiguousMove("Nd2".to_string())
+        );
+    }
+}
@@ -1,246 +1,242 @@
-use chess_engine::square;
-use indexmap::IndexMap;
+use chess_engine_macros::square;
 
 use crate::structs::*;
+use crate::zobrist::{castling_key, turn_key};
 
 impl Board {
     pub fn execute(&mut self, r#move: Move) {
-        self.halfmove_clock += 1;
-        self.fullmove_number += 1;
+        self.history.push(HistoryEntry {
+            r#move,
+            castling_rights: self.castling_rights,
+            enpassant_square: self.enpassant_square,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        });
+        self.hash ^= castling_key(&self.castling_rights) ^ self.enpassant_key();
+        // A null move isn't a move of the game, so it leaves the fifty-move count alone
+        if r#move.r#type != Null {
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Black {
+            self.fullmove_number += 1;
+        }
 
         match r#move.r#type {
             Normal | PawnJump => {
-                let piece = self.pieces.swap_remove(&r#move.from).unwrap();
-                let enpassant_rank = piece.color.get_enpassant_rank();
-                let r#type = piece.r#type;
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, piece.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, piece);
-
-                if r#type == Pawn {
-                    self.halfmove_clock = 0;
-                }
+                let piece = self.move_piece(r#move.from, r#move.to);
 
-                if r#move.r#type == PawnJump {
-                    self.enpassant_square = Some(square!(r#move.from.file enpassant_rank));
+                if piece.r#type == Pawn {
+                    self.halfmove_clock = 0;
                 }
             }
             Capture => {
-                let piece = self.pieces.swap_remove(&r#move.from).unwrap();
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, piece.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, piece);
+                self.remove_piece(r#move.to);
+                self.move_piece(r#move.from, r#move.to);
 
                 self.halfmove_clock = 0;
             }
             Promotion => {
-                let mut piece = self.pieces.swap_remove(&r#move.from).unwrap();
+                let mut piece = self.remove_piece(r#move.from);
                 piece.r#type = r#move.promotion.unwrap();
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, piece.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, piece);
+                self.put_piece(r#move.to, piece);
+
+                self.halfmove_clock = 0;
             }
             PromotionCapture => {
-                let mut piece = self.pieces.swap_remove(&r#move.from).unwrap();
+                self.remove_piece(r#move.to);
+                let mut piece = self.remove_piece(r#move.from);
                 piece.r#type = r#move.promotion.unwrap();
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, piece.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, piece);
+                self.put_piece(r#move.to, piece);
 
                 self.halfmove_clock = 0;
             }
             Enpassant => {
-                let piece = self.pieces.swap_remove(&r#move.from).unwrap();
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, piece.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, piece);
-
-                let captured_square = &r#move
-                    .from
-                    .offset(
-                        Into::<i8>::into(r#move.to.file) - Into::<i8>::into(r#move.from.file),
-                        0,
-                    )
-                    .unwrap();
-                self.pieces.swap_remove(captured_square);
-                self.attack_lines.swap_remove(captured_square);
+                self.move_piece(r#move.from, r#move.to);
+                self.remove_piece(square!(r#move.to.file r#move.from.rank));
 
                 self.halfmove_clock = 0;
             }
             Castle => {
-                let king = self.pieces.swap_remove(&r#move.from).unwrap();
-                self.attack_lines.swap_remove(&r#move.from);
-                self.attack_lines
-                    .insert(r#move.to, king.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, king);
-
-                let (rook_square_from, rook_square_to) = if r#move.to.file == File::C {
-                    (square!(A r#move.to.rank), square!(D r#move.to.rank))
-                } else {
-                    (square!(H r#move.to.rank), square!(F r#move.to.rank))
-                };
-                let rook = self.pieces.swap_remove(&rook_square_from).unwrap();
-                self.attack_lines.swap_remove(&rook_square_from);
-                self.attack_lines
-                    .insert(rook_square_to, rook.get_attack_lines(rook_square_to));
-                self.pieces.insert(rook_square_to, rook);
-
-                self.castling_rights
-                    .insert(self.turn, CastlingRights::new(false, false));
-            }
-        }
+                self.move_piece(r#move.from, r#move.to);
 
-        if let King = self.pieces.get(&r#move.to).unwrap().r#type {
-            self.kings.insert(self.turn, r#move.to);
-        }
+                let (rook_square_from, rook_square_to) = castle_rook_squares(&r#move);
+                self.move_piece(rook_square_from, rook_square_to);
 
-        if r#move.r#type != PawnJump {
-            self.enpassant_square = None;
+                self.castling_rights[self.turn as usize] = CastlingRights::new(false, false);
+            }
+            Null => {}
         }
 
-        self.history.push(r#move);
+        self.enpassant_square = if r#move.r#type == PawnJump {
+            let enpassant_rank = self.turn.get_enpassant_rank();
+            Some(square!(r#move.from.file enpassant_rank))
+        } else {
+            None
+        };
+
         self.turn = self.turn.opposite();
 
-        for color in [White, Black].iter() {
-            let CastlingRights {
-                kingside,
-                queenside,
-            } = *self.castling_rights.get(color).unwrap();
+        for color in [White, Black] {
             let piece_rank = color.get_piece_rank();
+            let king_home = self.bitboard(color, King) & square!(E piece_rank).bitboard() != 0;
+            let rooks = self.bitboard(color, Rook);
+            let castling_rights = &mut self.castling_rights[color as usize];
 
-            if kingside {
-                let king = self.pieces.get(&square!(E piece_rank));
-                let rook = self.pieces.get(&square!(H piece_rank));
-
-                if king.is_none()
-                    || rook.is_none()
-                    || king.unwrap().r#type != King
-                    || rook.unwrap().r#type != Rook
-                    || king.unwrap().color != rook.unwrap().color
-                {
-                    self.castling_rights
-                        .insert(*color, CastlingRights::new(false, queenside));
-                }
+            if !king_home || rooks & square!(H piece_rank).bitboard() == 0 {
+                castling_rights.kingside = false;
             }
-
-            if queenside {
-                let king = self.pieces.get(&square!(E piece_rank));
-                let rook = self.pieces.get(&square!(A piece_rank));
-
-                if king.is_none()
-                    || rook.is_none()
-                    || king.unwrap().r#type != King
-                    || rook.unwrap().r#type != Rook
-                    || king.unwrap().color != rook.unwrap().color
-                {
-                    self.castling_rights
-                        .insert(*color, CastlingRights::new(kingside, false));
-                }
+            if !king_home || rooks & square!(A piece_rank).bitboard() == 0 {
+                castling_rights.queenside = false;
             }
         }
+
+        self.hash ^= castling_key(&self.castling_rights)
+            ^ self.enpassant_key()
+            ^ turn_key(White);
     }
-    pub fn undo(
-        &mut self,
-        castling_rights: IndexMap<Color, CastlingRights>,
-        enpassant_square: Option<Square>,
-        halfmove_clock: u32,
-    ) -> Option<()> {
-        let r#move = self.history.pop()?;
+    /// Takes back the last move played, returning it.
+    pub fn undo(&mut self) -> Option<Move> {
+        let HistoryEntry {
+            r#move,
+            castling_rights,
+            enpassant_square,
+            halfmove_clock,
+            hash,
+        } = self.history.pop()?;
         self.turn = self.turn.opposite();
-		self.castling_rights = castling_rights;
-		self.enpassant_square = enpassant_square;
-		self.halfmove_clock = halfmove_clock;
-		self.fullmove_number -= 1;
+        self.castling_rights = castling_rights;
+        self.enpassant_square = enpassant_square;
+        self.halfmove_clock = halfmove_clock;
+        if self.turn == Black {
+            self.fullmove_number -= 1;
+        }
 
         match r#move.r#type {
             Normal | PawnJump => {
-                let piece = self.pieces.swap_remove(&r#move.to).unwrap();
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, piece.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, piece);
+                self.move_piece(r#move.to, r#move.from);
             }
             Capture => {
-                let piece = self.pieces.swap_remove(&r#move.to).unwrap();
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, piece.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, piece);
-
-                let captured = r#move.captured.unwrap();
-                self.attack_lines
-                    .insert(r#move.to, captured.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, captured);
+                self.move_piece(r#move.to, r#move.from);
+                self.put_piece(r#move.to, r#move.captured.unwrap());
             }
             Promotion => {
-                let mut piece = self.pieces.swap_remove(&r#move.to).unwrap();
+                let mut piece = self.remove_piece(r#move.to);
                 piece.r#type = Pawn;
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, piece.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, piece);
+                self.put_piece(r#move.from, piece);
             }
             PromotionCapture => {
-                let mut piece = self.pieces.swap_remove(&r#move.to).unwrap();
+                let mut piece = self.remove_piece(r#move.to);
                 piece.r#type = Pawn;
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, piece.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, piece);
-
-                let captured = r#move.captured.unwrap();
-                self.attack_lines
-                    .insert(r#move.to, captured.get_attack_lines(r#move.to));
-                self.pieces.insert(r#move.to, captured);
+                self.put_piece(r#move.from, piece);
+                self.put_piece(r#move.to, r#move.captured.unwrap());
             }
             Enpassant => {
-                let piece = self.pieces.swap_remove(&r#move.to).unwrap();
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, piece.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, piece);
-
-                let captured = r#move.captured.unwrap();
-                let captured_square = r#move
-                    .from
-                    .offset(
-                        Into::<i8>::into(r#move.to.file) - Into::<i8>::into(r#move.from.file),
-                        0,
-                    )
-                    .unwrap();
-                self.attack_lines
-                    .insert(captured_square, captured.get_attack_lines(captured_square));
-                self.pieces.insert(captured_square, captured);
+                self.move_piece(r#move.to, r#move.from);
+                self.put_piece(
+                    square!(r#move.to.file r#move.from.rank),
+                    r#move.captured.unwrap(),
+                );
             }
             Castle => {
-                let king = self.pieces.swap_remove(&r#move.to).unwrap();
-                self.attack_lines.swap_remove(&r#move.to);
-                self.attack_lines
-                    .insert(r#move.from, king.get_attack_lines(r#move.from));
-                self.pieces.insert(r#move.from, king);
-
-                let (rook_square_from, rook_square_to) = if r#move.to.file == File::C {
-                    (square!(A r#move.to.rank), square!(D r#move.to.rank))
-                } else {
-                    (square!(H r#move.to.rank), square!(F r#move.to.rank))
-                };
-                let rook = self.pieces.swap_remove(&rook_square_to).unwrap();
-                self.attack_lines.swap_remove(&rook_square_to);
-                self.attack_lines
-                    .insert(rook_square_from, rook.get_attack_lines(rook_square_from));
-                self.pieces.insert(rook_square_from, rook);
+                self.move_piece(r#move.to, r#move.from);
+
+                let (rook_square_from, rook_square_to) = castle_rook_squares(&r#move);
+                self.move_piece(rook_square_to, rook_square_from);
             }
+            Null => {}
         }
 
-        if let King = self.pieces.get(&r#move.from).unwrap().r#type {
-            self.kings.insert(self.turn, r#move.from);
+        self.hash = hash;
+
+        Some(r#move)
+    }
+
+    /// Passes the turn to the other side: the side to move flips and the en
+    /// passant square is cleared, with the hash updated to match.
+    pub fn make_null_move(&mut self) {
+        self.execute(Move::null());
+    }
+
+    pub fn unmake_null_move(&mut self) {
+        debug_assert!(self.history.last().is_some_and(|entry| entry.r#move.r#type == Null));
+        self.undo();
+    }
+
+    /// Takes back up to `n` moves, returning how many were actually undone.
+    pub fn undo_n(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.undo().is_some()).count()
+    }
+
+    /// Takes back the last move of each side, so the same player is to move again.
+    pub fn takeback(&mut self) -> bool {
+        if self.history.len() < 2 {
+            return false;
         }
+        self.undo_n(2) == 2
+    }
+}
 
-        Some(())
+/// Where the rook starts and ends for a castling move.
+pub fn castle_rook_squares(r#move: &Move) -> (Square, Square) {
+    if r#move.to.file == File::C {
+        (square!(A r#move.to.rank), square!(D r#move.to.rank))
+    } else {
+        (square!(H r#move.to.rank), square!(F r#move.to.rank))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen.to_string()).unwrap()
+    }
+
+    #[test]
+    fn null_move_round_trip() {
+        for fen in [
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 17 40",
+        ] {
+            let mut board = board(fen);
+            let before = board.clone();
+
+            board.make_null_move();
+            assert!(board.turn == before.turn.opposite());
+            assert!(board.enpassant_square.is_none());
+            assert_eq!(board.halfmove_clock, before.halfmove_clock);
+            assert_eq!(board.hash, board.compute_hash());
+            assert_ne!(board.hash, before.hash);
+
+            board.unmake_null_move();
+            assert!(board == before);
+            assert_eq!(board.to_fen(), fen);
+            assert_eq!(board.hash, before.hash);
+            assert!(board.enpassant_square == before.enpassant_square);
+            assert_eq!(board.halfmove_clock, before.halfmove_clock);
+            assert_eq!(board.fullmove_number, before.fullmove_number);
+            assert!(board.history.is_empty());
+        }
+    }
+
+    #[test]
+    fn repetitions_stop_at_null_moves() {
+        let mut board = board("4k3/8/8/8/8/8/8/R3K3 w - - 10 30");
+        let play = |board: &mut Board, moves: &[&str]| {
+            for r#move in moves {
+                board.execute(board.parse_uci_move(r#move).unwrap());
+            }
+        };
+        play(&mut board, &["a1a2", "e8d8", "a2a1", "d8e8"]);
+        assert_eq!(board.repetitions(), 1);
+
+        // The same position reached again behind a null move doesn't count
+        board.make_null_move();
+        board.make_null_move();
+        assert_eq!(board.repetitions(), 0);
+        play(&mut board, &["a1a2", "e8d8", "a2a1", "d8e8"]);
+        assert_eq!(board.repetitions(), 1);
+        assert_eq!(board.halfmove_clock, 18);
     }
 }
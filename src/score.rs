@@ -0,0 +1,120 @@
+use std::fmt;
+use std::ops::Neg;
+
+/// Deepest ply a mate score can encode.
+pub const MAX_PLY: i32 = 256;
+
+/// A search score in centipawns from the side to move's point of view.
+/// Scores within `MAX_PLY` of `MATE` encode a forced mate, counted in plies
+/// from the root, so that faster mates always score higher.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Score(pub i32);
+
+impl Score {
+    pub const DRAW: Score = Score(0);
+    pub const MATE: Score = Score(30_000);
+    /// Bigger than any real score, so it can bound a full window and still be negated.
+    pub const INFINITY: Score = Score(32_000);
+
+    /// The side to move is checkmated `ply` plies from the root.
+    pub fn mated_in(ply: usize) -> Score {
+        Score(-Score::MATE.0 + ply as i32)
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= Score::MATE.0 - MAX_PLY
+    }
+
+    /// Full moves until mate: positive when the side to move delivers it,
+    /// negative when it gets mated.
+    pub fn mate_moves(self) -> Option<i32> {
+        if !self.is_mate() {
+            None
+        } else if self.0 > 0 {
+            Some((Score::MATE.0 - self.0 + 1) / 2)
+        } else {
+            Some(-(Score::MATE.0 + self.0) / 2)
+        }
+    }
+
+    /// Mate scores are stored relative to the node rather than the root, so
+    /// an entry stays correct when the position is reached at another ply.
+    pub fn to_tt(self, ply: usize) -> Score {
+        if !self.is_mate() {
+            self
+        } else if self.0 > 0 {
+            Score(self.0 + ply as i32)
+        } else {
+            Score(self.0 - ply as i32)
+        }
+    }
+
+    /// Inverse of [`Score::to_tt`] for a position found at `ply`.
+    pub fn restored_from_tt(self, ply: usize) -> Score {
+        if !self.is_mate() {
+            self
+        } else if self.0 > 0 {
+            Score(self.0 - ply as i32)
+        } else {
+            Score(self.0 + ply as i32)
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+/// Formats the score the way UCI `info` lines expect, e.g. "cp 35" or "mate -2".
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mate_moves() {
+            Some(moves) => write!(f, "mate {}", moves),
+            None => write!(f, "cp {}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Score, MAX_PLY};
+
+    #[test]
+    fn table_round_trip() {
+        for score in [Score(35), Score(-1200), Score(Score::MATE.0 - 5), Score::mated_in(8)] {
+            for ply in [0, 1, 7, 40] {
+                assert_eq!(score.to_tt(ply).restored_from_tt(ply), score);
+            }
+        }
+        // A mate 3 plies below a node found at ply 5 is 3 plies below it at ply 2 too
+        let mate = Score(Score::MATE.0 - 8);
+        assert_eq!(mate.to_tt(5).restored_from_tt(2), Score(Score::MATE.0 - 5));
+        let mated = Score::mated_in(8);
+        assert_eq!(mated.to_tt(5).restored_from_tt(2), Score::mated_in(5));
+        assert_eq!(Score(250).to_tt(5).restored_from_tt(2), Score(250));
+    }
+
+    #[test]
+    fn mate_moves() {
+        assert_eq!(Score(Score::MATE.0 - 1).mate_moves(), Some(1));
+        assert_eq!(Score(Score::MATE.0 - 3).mate_moves(), Some(2));
+        assert_eq!(Score(Score::MATE.0 - 5).mate_moves(), Some(3));
+        assert_eq!(Score::mated_in(0).mate_moves(), Some(0));
+        assert_eq!(Score::mated_in(2).mate_moves(), Some(-1));
+        assert_eq!(Score::mated_in(4).mate_moves(), Some(-2));
+        assert_eq!(Score(Score::MATE.0 - MAX_PLY - 1).mate_moves(), None);
+        assert_eq!(Score(-90).mate_moves(), None);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Score(35).to_string(), "cp 35");
+        assert_eq!(Score(-120).to_string(), "cp -120");
+        assert_eq!(Score(Score::MATE.0 - 3).to_string(), "mate 2");
+        assert_eq!(Score::mated_in(4).to_string(), "mate -2");
+    }
+}
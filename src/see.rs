@@ -0,0 +1,141 @@
+use crate::bitboard::Bitboard;
+use crate::engine::get_piece_value;
+use crate::magic::{bishop_attacks, rook_attacks};
+use crate::structs::*;
+
+/// Stands in for the king so that capturing it always ends an exchange.
+const KING_VALUE: i32 = 10_000;
+
+impl Board {
+    /// Static exchange evaluation: the material the side to move wins or
+    /// loses if both sides keep recapturing on the target square with their
+    /// least valuable piece, each free to stop when recapturing would lose.
+    /// Sliders behind the pieces that already captured (x-rays) join in.
+    pub fn see(&self, r#move: &Move) -> i32 {
+        if r#move.r#type == Castle {
+            return 0;
+        }
+
+        let to = r#move.to;
+        let mut occupied = self.occupied();
+        if r#move.r#type == Enpassant {
+            occupied ^= Square {
+                file: to.file,
+                rank: r#move.from.rank,
+            }
+            .bitboard();
+        }
+
+        let diagonal = self.bitboard(White, Bishop)
+            | self.bitboard(Black, Bishop)
+            | self.bitboard(White, Queen)
+            | self.bitboard(Black, Queen);
+        let straight = self.bitboard(White, Rook)
+            | self.bitboard(Black, Rook)
+            | self.bitboard(White, Queen)
+            | self.bitboard(Black, Queen);
+        let mut attackers = self.attackers_to(to, occupied);
+
+        // gain[d] is the score for the side making capture d if the exchange stopped there
+        let mut gain = [0; 40];
+        let mut depth = 0;
+        gain[0] = r#move.captured.map_or(0, |p| value(p.r#type));
+        let mut attacker_value = value(self.mailbox[r#move.from.index()].unwrap().r#type);
+        if let Some(promotion) = r#move.promotion {
+            gain[0] += value(promotion) - value(Pawn);
+            attacker_value = value(promotion);
+        }
+        let mut from = r#move.from.bitboard();
+        let mut side = self.turn;
+
+        loop {
+            depth += 1;
+            gain[depth] = attacker_value - gain[depth - 1];
+
+            occupied ^= from;
+            attackers |= (bishop_attacks(to, occupied) & diagonal) | (rook_attacks(to, occupied) & straight);
+            attackers &= occupied;
+            side = side.opposite();
+
+            let Some((square, r#type)) = self.least_valuable_attacker(attackers, side) else {
+                break;
+            };
+            // The king may only recapture when nothing defends the square any more
+            if r#type == King && attackers & self.occupancy[side.opposite() as usize] != 0 {
+                break;
+            }
+            from = square;
+            attacker_value = value(r#type);
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+        gain[0]
+    }
+
+    fn least_valuable_attacker(&self, attackers: Bitboard, side: Color) -> Option<(Bitboard, PieceType)> {
+        [Pawn, Knight, Bishop, Rook, Queen, King]
+            .into_iter()
+            .find_map(|r#type| {
+                let pieces = attackers & self.bitboard(side, r#type);
+                (pieces != 0).then(|| (pieces & pieces.wrapping_neg(), r#type))
+            })
+    }
+}
+
+fn value(r#type: PieceType) -> i32 {
+    match r#type {
+        King => KING_VALUE,
+        _ => get_piece_value(&r#type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::*;
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let board = Board::from_fen(fen.to_string()).unwrap();
+        board.see(&board.parse_uci_move(uci).unwrap())
+    }
+
+    #[test]
+    fn simple_exchanges() {
+        // Undefended pawn
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        // Pawn defended by a pawn
+        assert_eq!(see("4k3/8/5p2/4p3/8/8/8/Q3K3 w - - 0 1", "a1e5"), 100 - 900);
+        // Equal trade
+        assert_eq!(see("4k3/8/3p4/4n3/3N4/8/8/4K3 b - - 0 1", "e5d3"), 0);
+        // Quiet move onto an attacked square
+        assert_eq!(see("4k3/8/5p2/8/8/8/8/4KN2 w - - 0 1", "f1g3"), 0);
+        assert_eq!(see("4k3/8/5p2/8/8/8/8/4KN2 w - - 0 1", "f1e3"), 0);
+        assert_eq!(see("4k3/8/8/8/5p2/8/8/4KN2 w - - 0 1", "f1e3"), -300);
+    }
+
+    #[test]
+    fn x_rays() {
+        // The second rook joins through the first one
+        assert_eq!(see("3r2k1/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), 100);
+        // Both sides have two rooks, so winning the pawn costs a rook
+        assert_eq!(see("3r2k1/3r4/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), -400);
+        // A bishop behind the queen backs up the capture
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -200);
+    }
+
+    #[test]
+    fn special_moves() {
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 0);
+        // Promoting wins the difference between the queen and the pawn
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), -100);
+        // A king can't recapture while the square is still attacked
+        assert_eq!(see("4k3/8/8/3p4/4K3/8/8/8 w - - 0 1", "e4d5"), 100);
+        assert_eq!(see("4k3/8/8/3r4/4K3/8/3Q4/3R4 b - - 0 1", "d5d2"), 900 - 500);
+        assert_eq!(see("4k3/8/8/3r4/4K3/8/3Q4/8 b - - 0 1", "d5d2"), 900);
+        assert_eq!(see("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"), 0);
+    }
+}
@@ -1,25 +1,54 @@
 use std::fmt;
 
-use chess_engine::square;
-use indexmap::IndexMap;
+use chess_engine_macros::square;
 use serde::{Deserialize, Serialize};
 pub use Color::*;
 pub use MoveType::*;
 pub use PieceType::*;
-#[derive(Eq, PartialEq)]
+
+use crate::bitboard::Bitboard;
+#[derive(Clone)]
 pub struct Board {
-    pub history: Vec<Move>,
-    pub pieces: IndexMap<Square, Piece>,
-    pub attack_lines: IndexMap<Square, Vec<Vec<Square>>>,
-    pub kings: IndexMap<Color, Square>,
+    pub history: Vec<HistoryEntry>,
+    /// Piece sets indexed by `[Color][PieceType]`, one bit per square (a1 = bit 0).
+    pub bitboards: [[Bitboard; 6]; 2],
+    /// All pieces of each color, kept in sync with `bitboards`.
+    pub occupancy: [Bitboard; 2],
+    /// Square-indexed lookup of the piece standing on each square.
+    pub(crate) mailbox: [Option<Piece>; 64],
 
     pub turn: Color,
-    pub castling_rights: IndexMap<Color, CastlingRights>,
+    pub castling_rights: [CastlingRights; 2],
     pub enpassant_square: Option<Square>,
     pub halfmove_clock: u32,
     pub fullmove_number: u32,
+    /// Polyglot-compatible Zobrist key of the current position.
+    pub hash: u64,
+}
+/// Two boards are equal when they describe the same position, regardless of
+/// the move history or the ids handed out to their pieces.
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.bitboards == other.bitboards
+            && self.turn == other.turn
+            && self.castling_rights == other.castling_rights
+            && self.enpassant_square == other.enpassant_square
+            && self.halfmove_clock == other.halfmove_clock
+            && self.fullmove_number == other.fullmove_number
+    }
+}
+impl Eq for Board {}
+/// A played move together with the state `execute` cannot recompute when
+/// the move is taken back.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct HistoryEntry {
+    pub r#move: Move,
+    pub castling_rights: [CastlingRights; 2],
+    pub enpassant_square: Option<Square>,
+    pub halfmove_clock: u32,
+    pub hash: u64,
 }
-#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Move {
     pub from: Square,
     pub to: Square,
@@ -103,6 +132,29 @@ impl Move {
             promotion: None,
         }
     }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn is_promotion(&self) -> bool {
+        self.promotion.is_some()
+    }
+
+    /// Neither a capture nor a promotion.
+    pub fn is_quiet(&self) -> bool {
+        !self.is_capture() && !self.is_promotion()
+    }
+
+    pub fn null() -> Move {
+        Move {
+            from: square!(A1),
+            to: square!(A1),
+            r#type: Null,
+            captured: None,
+            promotion: None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -179,8 +231,8 @@ impl Square {
     ];
 
     pub fn offset(&self, file_offset: i8, rank_offset: i8) -> Option<Square> {
-        let file = File::try_from((Into::<i8>::into(self.file) + file_offset) as i8);
-        let rank = Rank::try_from((Into::<i8>::into(self.rank) + rank_offset) as i8);
+        let file = File::try_from(Into::<i8>::into(self.file) + file_offset);
+        let rank = Rank::try_from(Into::<i8>::into(self.rank) + rank_offset);
 
         if let Ok(file) = file {
             if let Ok(rank) = rank {
@@ -190,13 +242,26 @@ impl Square {
 
         None
     }
+
+    /// Bit index of the square, counting from a1 = 0 to h8 = 63.
+    pub const fn index(self) -> usize {
+        self.rank as usize * 8 + self.file as usize
+    }
+
+    pub fn from_index(index: usize) -> Square {
+        Square::ALL[(7 - index / 8) * 8 + index % 8]
+    }
+
+    pub const fn bitboard(self) -> Bitboard {
+        1 << self.index()
+    }
 }
 impl fmt::Display for Square {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}{}", self.file, self.rank)
     }
 }
-#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Piece {
     pub id: u8,
     pub r#type: PieceType,
@@ -206,77 +271,6 @@ impl Piece {
     pub fn new(id: u8, r#type: PieceType, color: Color) -> Piece {
         Piece { id, r#type, color }
     }
-    pub fn get_attack_lines(&self, square: Square) -> Vec<Vec<Square>> {
-        let mut attack_lines = vec![];
-        match self.r#type {
-            Pawn => {
-                let team_multiplier = if self.color == White { 1 } else { -1 };
-                for (file, rank) in [(-1, team_multiplier), (1, team_multiplier)] {
-                    if let Some(target_square) = square.offset(file, rank) {
-                        attack_lines.push(vec![target_square]);
-                    }
-                }
-            }
-            Knight => {
-                for (file, rank) in Directions::KNIGHT {
-                    if let Some(target_square) = square.offset(file, rank) {
-                        attack_lines.push(vec![target_square]);
-                    }
-                }
-            }
-            Bishop => {
-                for (file, rank) in Directions::BISHOP {
-                    let mut current_square = square;
-                    let mut line = vec![];
-                    while let Some(target_square) = current_square.offset(file, rank) {
-                        line.push(target_square);
-                        current_square = target_square;
-                    }
-
-                    if !line.is_empty() {
-                        attack_lines.push(line);
-                    }
-                }
-            }
-            Rook => {
-                for (file, rank) in Directions::ROOK {
-                    let mut current_square = square;
-                    let mut line = vec![];
-                    while let Some(target_square) = current_square.offset(file, rank) {
-                        line.push(target_square);
-                        current_square = target_square;
-                    }
-
-                    if !line.is_empty() {
-                        attack_lines.push(line);
-                    }
-                }
-            }
-            Queen => {
-                for (file, rank) in Directions::QUEEN {
-                    let mut current_square = square;
-                    let mut line = vec![];
-                    while let Some(target_square) = current_square.offset(file, rank) {
-                        line.push(target_square);
-                        current_square = target_square;
-                    }
-
-                    if !line.is_empty() {
-                        attack_lines.push(line);
-                    }
-                }
-            }
-            King => {
-                for (file, rank) in Directions::KING {
-                    if let Some(target_square) = square.offset(file, rank) {
-                        attack_lines.push(vec![target_square]);
-                    }
-                }
-            }
-        }
-
-        attack_lines
-    }
 }
 #[derive(Copy, Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Color {
@@ -318,13 +312,6 @@ impl Color {
             Black => Rank::_6,
         }
     }
-
-    pub fn get_center_rank(self) -> Rank {
-        match self {
-            White => Rank::_4,
-            Black => Rank::_5,
-        }
-    }
 }
 #[derive(Clone, Copy, Eq, Hash, PartialEq, Deserialize, Serialize)]
 pub enum File {
@@ -445,7 +432,7 @@ impl fmt::Display for Rank {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct CastlingRights {
     pub kingside: bool,
     pub queenside: bool,
@@ -468,7 +455,7 @@ pub enum PieceType {
     Queen,
     King,
 }
-#[derive(Clone, Eq, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Eq, Deserialize, PartialEq, Serialize)]
 pub enum MoveType {
     Normal,
     Capture,
@@ -477,6 +464,8 @@ pub enum MoveType {
     PawnJump,
     Enpassant,
     Castle,
+    /// Passing the turn without moving, only used inside the search.
+    Null,
 }
 pub struct Directions {}
 
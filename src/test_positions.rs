@@ -0,0 +1,24 @@
+//! The standard perft positions, and a walk over the tree below a position,
+//! shared by the unit tests.
+
+use crate::structs::Board;
+
+pub const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+pub const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+pub const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+pub const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+pub const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+/// Calls `f` on `board` and on every position up to `depth` plies below it,
+/// leaving `board` as it was.
+pub fn for_each_position(board: &mut Board, depth: usize, f: &mut impl FnMut(&mut Board)) {
+    f(board);
+    if depth == 0 {
+        return;
+    }
+    for r#move in board.get_moves(false).0 {
+        board.execute(r#move);
+        for_each_position(board, depth - 1, f);
+        board.undo();
+    }
+}
@@ -0,0 +1,165 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::score::Score;
+use crate::structs::*;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bound {
+    /// The score is exact: it fell inside the search window.
+    Exact,
+    /// The search failed high, so the real score is at least this much.
+    Lower,
+    /// The search failed low, so the real score is at most this much.
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub depth: u8,
+    pub bound: Bound,
+    score: Score,
+    best_move: u16,
+}
+
+impl Entry {
+    /// Packs the entry into 64 bits: the move in bits 0-15, the score in
+    /// 16-31, the depth in 32-39 and the bound in 40-41. An empty slot reads
+    /// as a depth 0 upper bound.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Upper => 0,
+            Bound::Lower => 1,
+            Bound::Exact => 2,
+        };
+        self.best_move as u64
+            | (self.score.0 as i16 as u16 as u64) << 16
+            | (self.depth as u64) << 32
+            | bound << 40
+    }
+
+    fn unpack(data: u64) -> Entry {
+        Entry {
+            depth: (data >> 32) as u8,
+            bound: match (data >> 40) & 3 {
+                0 => Bound::Upper,
+                1 => Bound::Lower,
+                _ => Bound::Exact,
+            },
+            score: Score((data >> 16) as u16 as i16 as i32),
+            best_move: data as u16,
+        }
+    }
+
+    /// The stored score as seen from a node `ply` plies below the root.
+    pub fn score(&self, ply: usize) -> Score {
+        self.score.restored_from_tt(ply)
+    }
+
+    /// The score to return straight away if this entry settles the window,
+    /// following the fail-hard convention of `search`.
+    pub fn cutoff(&self, alpha: Score, beta: Score, ply: usize) -> Option<Score> {
+        let score = self.score(ply);
+        match self.bound {
+            Bound::Exact => Some(score),
+            Bound::Lower if score >= beta => Some(beta),
+            Bound::Upper if score <= alpha => Some(alpha),
+            _ => None,
+        }
+    }
+
+    pub fn best_move(&self) -> Option<u16> {
+        if self.best_move == 0 {
+            None
+        } else {
+            Some(self.best_move)
+        }
+    }
+}
+
+/// One table slot. The key is stored XORed with the packed entry, so a slot
+/// torn by two threads writing it at once no longer matches any position.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Fixed-size hash table of search results, indexed by the low bits of the
+/// position's Zobrist key. It needs no lock, so all search threads share it.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    /// Allocates the largest power-of-two number of entries that fits in `size_mb`.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let capacity = (size_mb.max(1) * 1024 * 1024 / size_of::<Slot>()).max(1);
+        let capacity = 1 << capacity.ilog2();
+        TranspositionTable {
+            slots: (0..capacity).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.slots.len() - 1)
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = &self.slots[self.index(hash)];
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.key.load(Ordering::Relaxed) ^ data == hash {
+            Some(Entry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the slot unless it holds a deeper result for the same position.
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: usize,
+        ply: usize,
+        bound: Bound,
+        score: Score,
+        best_move: Option<&Move>,
+    ) {
+        if self.probe(hash).is_some_and(|entry| entry.depth as usize > depth) {
+            return;
+        }
+
+        let data = Entry {
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            score: score.to_tt(ply),
+            best_move: best_move.map_or(0, pack_move),
+        }
+        .pack();
+        let slot = &self.slots[self.index(hash)];
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Packs the squares and promotion of a move into 16 bits, which is enough to
+/// find it again among the moves generated for the same position.
+pub fn pack_move(r#move: &Move) -> u16 {
+    let promotion = match r#move.promotion {
+        None => 0,
+        Some(Knight) => 1,
+        Some(Bishop) => 2,
+        Some(Rook) => 3,
+        Some(_) => 4,
+    };
+    (r#move.from.index() as u16) | (r#move.to.index() as u16) << 6 | promotion << 12
+}
@@ -0,0 +1,256 @@
+use std::io::{stdin, BufRead};
+use std::str::SplitWhitespace;
+use std::time::Duration;
+
+use chess_engine::engine::SearchResult;
+use chess_engine::fen::STARTING_FEN;
+use chess_engine::structs::*;
+use chess_engine::transposition_table::{Bound, DEFAULT_SIZE_MB};
+use chess_engine::{Engine, SearchEvent, SearchLimits};
+
+const MAX_HASH_MB: usize = 4096;
+const MAX_THREADS: usize = 256;
+
+/// State kept between UCI commands. The engine searches in the background
+/// so `stop` and `quit` are read while it is thinking.
+struct Uci {
+    engine: Engine,
+}
+
+/// Runs the UCI protocol on stdin/stdout until `quit` or end of input.
+/// `first_command` is a line that was already read before switching to UCI.
+pub fn uci_loop(first_command: Option<&str>) {
+    let mut uci = Uci {
+        engine: Engine::new(print_event),
+    };
+
+    if let Some(command) = first_command {
+        if !uci.handle(command) {
+            return;
+        }
+    }
+
+    for line in stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !uci.handle(&line) {
+            return;
+        }
+    }
+    uci.engine.stop();
+}
+
+impl Uci {
+    /// Handles one command line, returning false once the engine should exit.
+    fn handle(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name chess_engine {}", env!("CARGO_PKG_VERSION"));
+                println!("id author Jax-Hax");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_SIZE_MB, MAX_HASH_MB
+                );
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("option name Ponder type check default false");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => self.engine.new_game(),
+            Some("position") => match parse_position(tokens) {
+                Some(board) => self.engine.set_position(board),
+                None => println!("info string invalid position: {}", line),
+            },
+            Some("go") => self.engine.start(parse_go(tokens)),
+            Some("stop") => self.engine.stop(),
+            Some("ponderhit") => self.engine.ponderhit(),
+            Some("setoption") => {
+                self.engine.stop();
+                self.set_option(tokens);
+            }
+            Some("quit") => {
+                self.engine.stop();
+                return false;
+            }
+            // Unknown commands are ignored, as the protocol asks
+            _ => {}
+        }
+        true
+    }
+
+    fn set_option(&mut self, mut tokens: SplitWhitespace) {
+        if tokens.next() != Some("name") {
+            return;
+        }
+        let name: Vec<&str> = tokens.by_ref().take_while(|t| *t != "value").collect();
+        let value = tokens.collect::<Vec<&str>>().join(" ");
+
+        match name.join(" ").to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(size_mb) => self.engine.set_hash_size(size_mb.clamp(1, MAX_HASH_MB)),
+                Err(_) => println!("info string invalid Hash value: {}", value),
+            },
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => self.engine.set_threads(threads.clamp(1, MAX_THREADS)),
+                Err(_) => println!("info string invalid Threads value: {}", value),
+            },
+            // Pondering needs nothing set up beforehand; the GUI asks for it with `go ponder`
+            "ponder" => {}
+            _ => println!("info string unknown option: {}", name.join(" ")),
+        }
+    }
+}
+
+fn print_event(event: SearchEvent) {
+    match event {
+        SearchEvent::Progress(result) => print_info(&result),
+        SearchEvent::Finished(result) => print_best_move(&result),
+    }
+}
+
+/// Suggests the reply the engine expects as the move to ponder on.
+fn print_best_move(result: &SearchResult) {
+    match (result.best_move, result.pv.get(1)) {
+        (Some(best_move), Some(reply)) if result.pv[0] == best_move => {
+            println!("bestmove {} ponder {}", best_move.to_uci(), reply.to_uci())
+        }
+        (Some(best_move), _) => println!("bestmove {}", best_move.to_uci()),
+        (None, _) => println!("bestmove 0000"),
+    }
+}
+
+fn print_info(result: &SearchResult) {
+    let millis = result.elapsed.as_millis().max(1);
+    let pv: String = result.pv.iter().map(|m| format!(" {}", m.to_uci())).collect();
+    let pv = if pv.is_empty() { pv } else { format!(" pv{}", pv) };
+    let bound = match result.bound {
+        Bound::Exact => "",
+        Bound::Lower => " lowerbound",
+        Bound::Upper => " upperbound",
+    };
+    println!(
+        "info depth {} score {}{} nodes {} nps {} time {}{}",
+        result.depth,
+        result.score,
+        bound,
+        result.nodes,
+        result.nodes as u128 * 1000 / millis,
+        result.elapsed.as_millis(),
+        pv
+    );
+}
+
+/// Parses `startpos|fen <fen> [moves <move>...]`.
+fn parse_position(mut tokens: SplitWhitespace) -> Option<Board> {
+    let mut board = match tokens.next()? {
+        "startpos" => Board::from_fen(STARTING_FEN.to_string()).ok()?,
+        "fen" => {
+            let fen: Vec<&str> = tokens.by_ref().take_while(|t| *t != "moves").collect();
+            Board::from_fen(fen.join(" ")).ok()?
+        }
+        _ => return None,
+    };
+
+    // `take_while` above already consumed the "moves" keyword after a FEN
+    for token in tokens.skip_while(|t| *t == "moves") {
+        let r#move = board.parse_uci_move(token).ok()?;
+        board.execute(r#move);
+    }
+    Some(board)
+}
+
+/// Parses the arguments of `go`. Some GUIs send a negative clock once it
+/// has run out, which is read as no time left rather than no limit.
+fn parse_go(mut tokens: SplitWhitespace) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    while let Some(token) = tokens.next() {
+        let mut number = || {
+            let number = tokens.next()?.parse::<i64>().ok()?;
+            Some(number.max(0) as u64)
+        };
+        match token {
+            "depth" => limits.depth = number().map(|n| n as usize),
+            "movetime" => limits.movetime = number().map(Duration::from_millis),
+            "wtime" => limits.wtime = number().map(Duration::from_millis),
+            "btime" => limits.btime = number().map(Duration::from_millis),
+            "winc" => limits.winc = number().map(Duration::from_millis),
+            "binc" => limits.binc = number().map(Duration::from_millis),
+            "movestogo" => limits.movestogo = number().map(|n| n as u32),
+            "nodes" => limits.nodes = number(),
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            _ => {}
+        }
+    }
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_go, parse_position, STARTING_FEN};
+
+    fn position(arguments: &str) -> Option<String> {
+        parse_position(arguments.split_whitespace()).map(|board| board.to_fen())
+    }
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(position("startpos").unwrap(), STARTING_FEN);
+        assert_eq!(
+            position("startpos moves e2e4 c7c5 g1f3").unwrap(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(position(&format!("fen {}", fen)).unwrap(), fen);
+        assert_eq!(
+            position(&format!("fen {} moves e2e3 h5h6", fen)).unwrap(),
+            "8/2p5/3p3r/KP6/1R3p1k/4P3/6P1/8 w - - 1 2"
+        );
+        assert_eq!(
+            position("fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q").unwrap(),
+            "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_positions() {
+        assert!(position("").is_none());
+        assert!(position("somewhere").is_none());
+        assert!(position("fen 8/8/8 w - - 0 1").is_none());
+        assert!(position("startpos moves e2e5").is_none());
+        assert!(position("startpos moves e2e4 e2e4").is_none());
+    }
+
+    #[test]
+    fn parses_go() {
+        let limits = parse_go("wtime 60000 btime 50000 winc 1000 binc 500 movestogo 20".split_whitespace());
+        assert_eq!(limits.wtime, Some(Duration::from_secs(60)));
+        assert_eq!(limits.btime, Some(Duration::from_secs(50)));
+        assert_eq!(limits.winc, Some(Duration::from_secs(1)));
+        assert_eq!(limits.binc, Some(Duration::from_millis(500)));
+        assert_eq!(limits.movestogo, Some(20));
+        assert!(!limits.infinite && !limits.ponder);
+
+        let limits = parse_go("depth 7 nodes 5000 movetime 250 ponder".split_whitespace());
+        assert_eq!(limits.depth, Some(7));
+        assert_eq!(limits.nodes, Some(5000));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(250)));
+        assert!(limits.ponder);
+
+        assert!(parse_go("infinite".split_whitespace()).infinite);
+        // Unknown tokens and missing numbers are skipped
+        let limits = parse_go("searchmoves e2e4 depth".split_whitespace());
+        assert_eq!(limits.depth, None);
+    }
+
+    /// An exhausted clock is no time left, not an unlimited search.
+    #[test]
+    fn negative_clocks_are_empty() {
+        let limits = parse_go("wtime -150 btime 1000 winc -5".split_whitespace());
+        assert_eq!(limits.wtime, Some(Duration::ZERO));
+        assert_eq!(limits.winc, Some(Duration::ZERO));
+        assert!(limits.time_budget(chess_engine::structs::White).unwrap() <= Duration::from_millis(1));
+    }
+}